@@ -0,0 +1,357 @@
+use cargo_metadata::{DependencyKind, camino::Utf8Path};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// Options controlling how dependencies are detected and hoisted
+#[derive(Debug, Clone, Serialize)]
+pub struct Config {
+    /// Minimum number of occurrences to consider a dependency common
+    pub min_occurrences: u32,
+    /// Minimum percentage of workspace members using a dependency to consider it common, the
+    /// stricter of this and `min_occurrences` applies
+    pub threshold_percent: Option<u8>,
+    /// Hoist every dependency regardless of how many members use it, overriding the thresholds
+    pub all: bool,
+    /// Workspaces to process independently, the current directory when empty
+    pub workspace_roots: Vec<PathBuf>,
+    /// Cargo.toml of the workspace to process, instead of `workspace_roots`
+    pub manifest_path: Option<PathBuf>,
+    /// Suppress results printed to stdout
+    pub quiet: bool,
+    /// Write the updated root Cargo.toml to this path instead of in place (`-` for stdout),
+    /// leaving member Cargo.toml files untouched
+    pub output: Option<PathBuf>,
+    /// Print why each dependency was or wasn't hoisted
+    pub explain: bool,
+    /// Dependencies to leave untouched so members keep their explicit `version`
+    pub keep_version: Vec<String>,
+    /// Dependency kinds to hoist, all kinds when empty
+    pub only_kinds: Vec<DependencyKind>,
+    /// Fail if any manifest would be updated
+    pub locked: bool,
+    /// Warn about members declaring the same crate more than once for the same kind
+    pub warn_duplicates: bool,
+    /// Add the common dependencies to the root workspace.dependencies
+    pub update_root: bool,
+    /// Convert members to the workspace dependencies
+    pub update_members: bool,
+    /// Which of the features members enable move to the workspace entry
+    pub features_strategy: FeaturesStrategy,
+    /// Hoist dependencies declared with both a `path` and a `version`, the workspace entry
+    /// carrying both
+    pub hoist_path_versions: bool,
+    /// Skip dependencies whose version requirement is clearly pre-1.0
+    pub skip_pre_1: bool,
+    /// Hoist dependencies whose version requirement is a wildcard (`*`) instead of skipping them
+    pub allow_wildcard: bool,
+    /// Write version requirements to the workspace in their shortest equivalent form
+    pub normalize_versions: bool,
+    /// Group the root `workspace.dependencies` entries by dependency kind under comment headers
+    pub group_by_kind: bool,
+    /// Keep the root `workspace.dependencies` entries sorted by name, within their group with
+    /// `group_by_kind`
+    pub keep_sorted: bool,
+    /// Form the rewritten member entries and the new root entries are written in
+    pub table_style: TableStyle,
+    /// Form the new root `workspace.dependencies` entries are written in, `table_style` when
+    /// unset
+    pub root_table_style: Option<TableStyle>,
+    /// Warn about members whose `Cargo.lock` version doesn't satisfy the requirement they would
+    /// inherit
+    pub check_lock: bool,
+    /// Move the `[patch]` entries enough members declare identically to the root, where cargo
+    /// applies them
+    pub apply_to_patch: bool,
+    /// Dependency kinds a dependency must be used as across the workspace to be hoisted
+    pub require_kinds: Vec<DependencyKind>,
+    /// Write a unified diff of every manifest change to this path instead of updating the
+    /// manifests, `run` truncates it while `run_workspace` appends to it
+    pub emit_patch: Option<PathBuf>,
+    /// Append a timestamped line for every file read and written to this path, for auditing
+    pub trace_file: Option<PathBuf>,
+    /// Process a standalone package that isn't part of a workspace, or more members than
+    /// `max_members`
+    pub force: bool,
+    /// Refuse to update workspaces with more members than this, unless `force` is set
+    pub max_members: Option<usize>,
+    /// Fail when a dependency members require in several ways has more distinct requirements per
+    /// member using it than this ratio
+    pub max_fragmentation: Option<f64>,
+    /// Create the root `[workspace]` table when it's missing instead of failing
+    pub init_workspace_section: bool,
+    /// Number of context lines around each change in the `emit_patch` diff
+    pub diff_context: usize,
+    /// Cache the `cargo metadata` output in this file, reused until a manifest changes
+    pub metadata_cache: Option<PathBuf>,
+    /// Report the changes without writing any file
+    pub dry_run: bool,
+    /// Print the diff of the root manifest instead of writing anything
+    pub root_diff: bool,
+    /// Ask on the terminal before writing the manifests
+    pub confirm: bool,
+    /// Dependencies left unhoisted when used as the given kind only
+    pub exclude: Vec<(DependencyKind, String)>,
+    /// Leave git dependencies unhoisted
+    pub exclude_git: bool,
+    /// Names of the registries whose dependencies are left unhoisted, `crates-io` included
+    pub exclude_registries: Vec<String>,
+    /// Leave optional dependencies unhoisted, they're tied to the features of their member
+    pub exclude_optional: bool,
+    /// Remove root `workspace.dependencies` entries no member inherits anymore
+    pub prune: bool,
+    /// Rename the root `workspace.dependencies` entries spelled differently from the crate members
+    /// use instead of failing
+    pub fix_root_spelling: bool,
+    /// Remove the dependency tables left empty in the members
+    pub prune_empty_tables: bool,
+    /// Skip the members whose `Cargo.toml` is a symlink instead of updating its target
+    pub no_follow_symlinks: bool,
+    /// Directories, relative to the workspace root, of peripheral members (e.g. examples) whose
+    /// dependencies are converted but not counted
+    pub dev_only_members: Vec<PathBuf>,
+    /// Only print how widely each dependency is duplicated, without modifying anything
+    pub report: bool,
+    /// Only print the characters spent on the version declarations hoisting would remove,
+    /// without modifying anything
+    pub dedupe_only: bool,
+    /// Only print the root `workspace.dependencies` block hoisting would write, leaving every
+    /// manifest untouched
+    pub print_workspace_deps: bool,
+    /// Only print the root `workspace.dependencies` entries no member inherits, failing with
+    /// `locked` when there are any
+    pub report_unused: bool,
+    /// Only print the common dependencies, without modifying anything
+    pub list: bool,
+    /// Format of the printed report
+    pub format: OutputFormat,
+    /// Only hoist these dependencies, regardless of the threshold, all when empty
+    pub only_deps: Vec<String>,
+    /// What to do when members require different versions of a dependency
+    pub on_conflict: ConflictPolicy,
+    /// Where the versions of the new root entries come from
+    pub version_source: VersionSource,
+    /// Discover the members from the root `workspace.members` globs instead of running
+    /// `cargo metadata`, only hoisting dependencies declared with a plain version string
+    pub no_metadata: bool,
+    /// Fail instead of processing an enclosing workspace when a workspace root isn't one itself
+    pub no_recurse: bool,
+    /// Only consider the members with files changed since this git ref
+    pub since: Option<String>,
+    /// Only consider the members whose package names this file lists, one per line
+    pub members_from_file: Option<PathBuf>,
+}
+
+/// How version requirement disagreements between members are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Abort the run
+    Error,
+    /// Skip the dependency with a warning, leaving the members untouched
+    Warn,
+    /// Hoist the first version requirement seen
+    #[default]
+    First,
+}
+
+/// Which features of a common dependency move from the members to its workspace entry, members
+/// keeping the ones left out next to `workspace = true`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeaturesStrategy {
+    /// None, every member keeps its own list
+    #[default]
+    None,
+    /// The features every member enables
+    Intersection,
+    /// Every feature any member enables
+    Union,
+    /// The feature list, only when every member enables exactly the same one
+    Uniform,
+}
+
+/// Where the versions of the new `workspace.dependencies` entries come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionSource {
+    /// The requirement members declare
+    #[default]
+    Manifest,
+    /// The exact version members resolve to in `Cargo.lock`, e.g. `=1.0.200`
+    Lockfile,
+}
+
+/// Form dependency entries are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TableStyle {
+    /// Keep the form of each rewritten member entry, plain version strings and new root entries
+    /// become inline tables
+    #[default]
+    Preserve,
+    /// Inline tables, e.g. `serde = { workspace = true }`
+    Inline,
+    /// Sections, e.g. `[dependencies.serde]`
+    Section,
+}
+
+/// Formats results can be printed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// A JSON document per workspace
+    Json,
+    /// A GitHub Actions notice per updated manifest, reports are printed as text
+    GithubAnnotations,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_occurrences: 2,
+            threshold_percent: None,
+            all: false,
+            workspace_roots: Vec::new(),
+            manifest_path: None,
+            quiet: false,
+            output: None,
+            explain: false,
+            keep_version: Vec::new(),
+            only_kinds: Vec::new(),
+            locked: false,
+            warn_duplicates: false,
+            update_root: true,
+            update_members: true,
+            features_strategy: FeaturesStrategy::None,
+            hoist_path_versions: false,
+            skip_pre_1: false,
+            allow_wildcard: false,
+            normalize_versions: false,
+            group_by_kind: false,
+            keep_sorted: false,
+            table_style: TableStyle::Preserve,
+            root_table_style: None,
+            apply_to_patch: false,
+            check_lock: false,
+            require_kinds: Vec::new(),
+            emit_patch: None,
+            trace_file: None,
+            force: false,
+            max_members: None,
+            max_fragmentation: None,
+            init_workspace_section: false,
+            diff_context: 3,
+            metadata_cache: None,
+            dry_run: false,
+            root_diff: false,
+            confirm: false,
+            exclude: Vec::new(),
+            exclude_git: false,
+            exclude_registries: Vec::new(),
+            exclude_optional: false,
+            prune: false,
+            fix_root_spelling: false,
+            prune_empty_tables: false,
+            no_follow_symlinks: false,
+            dev_only_members: Vec::new(),
+            report: false,
+            dedupe_only: false,
+            print_workspace_deps: false,
+            report_unused: false,
+            list: false,
+            format: OutputFormat::Text,
+            only_deps: Vec::new(),
+            on_conflict: ConflictPolicy::First,
+            version_source: VersionSource::Manifest,
+            no_metadata: false,
+            no_recurse: false,
+            since: None,
+            members_from_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Whether the root manifest is printed to stdout, which must stay free of other results
+    pub fn output_to_stdout(&self) -> bool {
+        self.output.as_deref() == Some(Path::new("-"))
+    }
+
+    /// Whether only a report is printed, leaving every manifest untouched
+    pub fn is_query(&self) -> bool {
+        self.report
+            || self.dedupe_only
+            || self.print_workspace_deps
+            || self.report_unused
+            || self.list
+    }
+
+    /// Whether results meant for stdout are suppressed, diagnostics go through `tracing`
+    pub fn is_quiet(&self) -> bool {
+        self.quiet || self.output_to_stdout()
+    }
+
+    /// The number of occurrences required to hoist a dependency in a workspace of `members`
+    pub fn required_occurrences(&self, members: usize) -> u32 {
+        if self.all {
+            return 1;
+        }
+        let from_percent = self.threshold_percent.map_or(0, |percent| {
+            (members as u64 * u64::from(percent)).div_ceil(100) as u32
+        });
+        self.min_occurrences.max(from_percent)
+    }
+
+    /// Whether dependencies of `kind` are selected for hoisting
+    pub(crate) fn includes_kind(&self, kind: DependencyKind) -> bool {
+        self.only_kinds.is_empty() || self.only_kinds.contains(&normalize_kind(kind))
+    }
+
+    /// Whether `name` is excluded from hoisting when used as `kind`
+    pub(crate) fn excludes(&self, name: &str, kind: DependencyKind) -> bool {
+        self.exclude
+            .iter()
+            .any(|(excluded_kind, excluded)| *excluded_kind == kind && excluded == name)
+    }
+
+    /// Whether the member at `manifest_path` lives in one of the `dev_only_members` directories
+    pub(crate) fn is_peripheral(
+        &self,
+        workspace_root: &Utf8Path,
+        manifest_path: &Utf8Path,
+    ) -> bool {
+        manifest_path
+            .strip_prefix(workspace_root)
+            .is_ok_and(|relative| {
+                self.dev_only_members
+                    .iter()
+                    .any(|dir| relative.as_std_path().starts_with(dir))
+            })
+    }
+
+    /// Form the new root `workspace.dependencies` entries are written in
+    pub(crate) fn root_style(&self) -> TableStyle {
+        self.root_table_style.unwrap_or(self.table_style)
+    }
+
+    /// Whether a dependency used as `kinds` across the workspace covers every required kind
+    pub(crate) fn covers_required_kinds(&self, kinds: &HashSet<DependencyKind>) -> bool {
+        self.require_kinds
+            .iter()
+            .all(|kind| kinds.contains(&normalize_kind(*kind)))
+    }
+}
+
+/// Dependencies of an unknown kind are declared in `[dependencies]`
+pub(crate) fn normalize_kind(kind: DependencyKind) -> DependencyKind {
+    match kind {
+        DependencyKind::Unknown => DependencyKind::Normal,
+        kind => kind,
+    }
+}
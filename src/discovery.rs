@@ -0,0 +1,300 @@
+use crate::{
+    AppError, AppResult, DEPENDENCY_TABLES, error::io_err, is_inherited, read_manifest,
+    workspace_dependency_version,
+};
+use cargo_metadata::{
+    DependencyKind, Metadata,
+    camino::{Utf8Path, Utf8PathBuf},
+    semver::VersionReq,
+};
+use colored::Colorize;
+use serde_json::{Value, json};
+use std::{env, fs, path::Path};
+use tracing::{debug, warn};
+
+/// Builds the workspace metadata from the manifests alone, expanding the root
+/// `workspace.members` globs instead of running `cargo metadata`.
+///
+/// Only dependencies declared with a plain version string, or inheriting a workspace entry that
+/// has one, are reported.
+pub(crate) fn discover_metadata(workspace_path: &Path) -> AppResult<Metadata> {
+    let workspace_root = find_workspace_root(workspace_path)?;
+    let root_manifest_path = workspace_root.join("Cargo.toml");
+    let (_, root) = read_manifest(&root_manifest_path)?;
+    let workspace = &root["workspace"];
+
+    let member_dirs = member_dirs(&workspace_root, &root)?;
+    let target_directory = target_directory(&workspace_root);
+
+    let mut packages = Vec::new();
+    for dir in &member_dirs {
+        let manifest_path = dir.join("Cargo.toml");
+        match package_json(&manifest_path, workspace.get("dependencies")) {
+            Ok(package) => packages.push(package),
+            // Skipping broken members is the point of this mode
+            Err(e) => warn!(
+                "{} skipping member {}: {}",
+                "Warning:".yellow().bold(),
+                manifest_path,
+                e
+            ),
+        }
+    }
+    debug!("Discovered {} workspace members", packages.len());
+
+    let workspace_members: Vec<Value> = packages.iter().map(|p| p["id"].clone()).collect();
+    let metadata = json!({
+        "packages": packages,
+        "workspace_members": workspace_members,
+        "resolve": null,
+        "workspace_root": workspace_root,
+        "target_directory": target_directory,
+        "metadata": workspace.get("metadata").map_or(Value::Null, toml_to_json),
+        "version": 1,
+    });
+    serde_json::from_value(metadata)
+        .map_err(|e| AppError::Metadata(format!("Failed to build metadata: {}", e)))
+}
+
+/// The member directories the root manifest `root` of the workspace at `workspace_root` lists,
+/// expanding its `workspace.members` globs
+pub(crate) fn member_dirs(
+    workspace_root: &Utf8Path,
+    root: &toml_edit::DocumentMut,
+) -> AppResult<Vec<Utf8PathBuf>> {
+    let workspace = &root["workspace"];
+    let patterns = string_array(workspace.get("members"));
+    let excluded = excluded_paths(workspace_root, workspace);
+    let target_directory = target_directory(workspace_root);
+
+    let mut member_dirs = Vec::new();
+    if root.contains_key("package") {
+        member_dirs.push(workspace_root.to_path_buf());
+    }
+    for pattern in &patterns {
+        let pattern = workspace_root.join(pattern);
+        let paths = glob::glob(pattern.as_str()).map_err(|e| {
+            AppError::Metadata(format!(
+                "invalid workspace member pattern '{}': {}",
+                pattern, e
+            ))
+        })?;
+        for path in paths.flatten() {
+            let Ok(dir) = Utf8PathBuf::from_path_buf(path) else {
+                continue;
+            };
+            if !dir.join("Cargo.toml").is_file()
+                || excluded.iter().any(|excluded| dir.starts_with(excluded))
+                || member_dirs.contains(&dir)
+            {
+                continue;
+            }
+            // Build artifacts and tool state can hold manifests a broad glob matches, e.g. a
+            // packaged crate under `target/package`
+            if in_target_directory(workspace_root, &target_directory, &dir)
+                || is_hidden(workspace_root, &dir)
+            {
+                debug!("Not discovering {} as a member, it holds no sources", dir);
+                continue;
+            }
+            member_dirs.push(dir);
+        }
+    }
+    Ok(member_dirs)
+}
+
+/// The directories listed in the root `workspace.exclude`, along with everything below them
+pub(crate) fn excluded_paths(
+    workspace_root: &Utf8Path,
+    workspace: &toml_edit::Item,
+) -> Vec<Utf8PathBuf> {
+    string_array(workspace.get("exclude"))
+        .into_iter()
+        .map(|path| workspace_root.join(path))
+        .collect()
+}
+
+/// The directory cargo builds the workspace at `workspace_root` into: `CARGO_TARGET_DIR`,
+/// relative to the current directory like cargo reads it, or else `target` under the root
+fn target_directory(workspace_root: &Utf8Path) -> Utf8PathBuf {
+    let Some(dir) = env::var_os("CARGO_TARGET_DIR").filter(|dir| !dir.is_empty()) else {
+        return workspace_root.join("target");
+    };
+    let dir = fs::canonicalize(&dir)
+        .or_else(|_| std::path::absolute(&dir))
+        .unwrap_or_else(|_| dir.into());
+    Utf8PathBuf::from_path_buf(dir).unwrap_or_else(|_| workspace_root.join("target"))
+}
+
+/// Whether `path` lies in the `target_directory` of the workspace at `workspace_root`, unless
+/// the workspace itself does
+pub(crate) fn in_target_directory(
+    workspace_root: impl AsRef<Path>,
+    target_directory: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+) -> bool {
+    let target_directory = target_directory.as_ref();
+    path.as_ref().starts_with(target_directory)
+        && !workspace_root.as_ref().starts_with(target_directory)
+}
+
+/// Whether `dir` lies in a hidden directory under `workspace_root`, e.g. `.git`
+fn is_hidden(workspace_root: &Utf8Path, dir: &Utf8Path) -> bool {
+    dir.strip_prefix(workspace_root).is_ok_and(|relative| {
+        relative
+            .components()
+            .any(|component| component.as_str().starts_with('.'))
+    })
+}
+
+/// The closest directory from `path` upwards whose manifest has a `[workspace]` table
+fn find_workspace_root(path: &Path) -> AppResult<Utf8PathBuf> {
+    let path = fs::canonicalize(path).map_err(|e| io_err(e, path))?;
+    for dir in path.ancestors() {
+        let manifest_path = dir.join("Cargo.toml");
+        if manifest_path.is_file() {
+            let (_, doc) = read_manifest(&manifest_path)?;
+            if doc.contains_key("workspace") {
+                return Utf8PathBuf::from_path_buf(dir.to_path_buf()).map_err(|dir| {
+                    AppError::Metadata(format!("non UTF-8 path '{}'", dir.display()))
+                });
+            }
+        }
+    }
+    Err(AppError::Metadata(format!(
+        "no workspace found from '{}'",
+        path.display()
+    )))
+}
+
+/// The `cargo metadata` JSON of the package at `manifest_path`, resolving inherited dependencies
+/// from the root `workspace_deps`
+fn package_json(
+    manifest_path: &Utf8PathBuf,
+    workspace_deps: Option<&toml_edit::Item>,
+) -> AppResult<Value> {
+    let (_, doc) = read_manifest(manifest_path)?;
+    let package = doc
+        .get("package")
+        .ok_or_else(|| AppError::Metadata("no [package] table".to_string()))?;
+    let name = package
+        .get("name")
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| AppError::Metadata("missing package name".to_string()))?;
+    // Inherited or missing versions don't matter for hoisting
+    let version = package
+        .get("version")
+        .and_then(|version| version.as_str())
+        .unwrap_or("0.0.0");
+
+    let mut dependencies = Vec::new();
+    for (table_name, kind) in DEPENDENCY_TABLES {
+        let Some(deps) = doc.get(table_name).and_then(|deps| deps.as_table_like()) else {
+            continue;
+        };
+        for (dep_name, item) in deps.iter() {
+            // Inheriting members must keep being counted, or their entries would look unused
+            let workspace_dep = workspace_deps
+                .filter(|_| is_inherited(item))
+                .and_then(|deps| deps.get(dep_name));
+            // Cargo refuses such a member, e.g. one of a nested workspace expecting to inherit
+            // from the outer one, and it's nothing this workspace can hoist
+            if is_inherited(item) && workspace_dep.is_none() {
+                warn!(
+                    "{} '{}' in {} inherits a workspace entry the root doesn't declare, leaving it untouched",
+                    "Warning:".yellow().bold(),
+                    dep_name,
+                    manifest_path
+                );
+                continue;
+            }
+            let req = match workspace_dep {
+                Some(workspace_dep) => workspace_dependency_version(workspace_dep),
+                None => item.as_str(),
+            };
+            let Some(req) = req.filter(|req| VersionReq::parse(req).is_ok()) else {
+                continue;
+            };
+            let uses_default_features = item
+                .get("default-features")
+                .or_else(|| workspace_dep.and_then(|dep| dep.get("default-features")))
+                .and_then(|default_features| default_features.as_bool())
+                .unwrap_or(true);
+            dependencies.push(json!({
+                "name": dep_name,
+                "source": "registry+https://github.com/rust-lang/crates.io-index",
+                "req": req,
+                "kind": match kind {
+                    DependencyKind::Development => "dev",
+                    DependencyKind::Build => "build",
+                    _ => "normal",
+                },
+                "optional": false,
+                "uses_default_features": uses_default_features,
+                "features": [],
+                "target": null,
+                "rename": null,
+                "registry": null,
+                "path": null,
+            }));
+        }
+    }
+
+    Ok(json!({
+        "name": name,
+        "version": version,
+        "id": format!("path+file://{}#{}@{}", manifest_path.parent().unwrap_or(manifest_path), name, version),
+        "dependencies": dependencies,
+        "targets": [],
+        "features": {},
+        "manifest_path": manifest_path,
+    }))
+}
+
+fn string_array(item: Option<&toml_edit::Item>) -> Vec<String> {
+    item.and_then(|item| item.as_array())
+        .map(|array| {
+            array
+                .iter()
+                .filter_map(|value| value.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Converts `workspace.metadata` so it can be read as `Metadata::workspace_metadata`
+fn toml_to_json(item: &toml_edit::Item) -> Value {
+    match item {
+        toml_edit::Item::Value(value) => toml_value_to_json(value),
+        toml_edit::Item::Table(table) => table
+            .iter()
+            .map(|(key, item)| (key.to_string(), toml_to_json(item)))
+            .collect(),
+        toml_edit::Item::ArrayOfTables(tables) => tables
+            .iter()
+            .map(|table| {
+                table
+                    .iter()
+                    .map(|(key, item)| (key.to_string(), toml_to_json(item)))
+                    .collect::<serde_json::Map<_, _>>()
+            })
+            .map(Value::Object)
+            .collect(),
+        toml_edit::Item::None => Value::Null,
+    }
+}
+
+pub(crate) fn toml_value_to_json(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => json!(s.value()),
+        toml_edit::Value::Integer(i) => json!(i.value()),
+        toml_edit::Value::Float(f) => json!(f.value()),
+        toml_edit::Value::Boolean(b) => json!(b.value()),
+        toml_edit::Value::Datetime(d) => json!(d.value().to_string()),
+        toml_edit::Value::Array(array) => array.iter().map(toml_value_to_json).collect(),
+        toml_edit::Value::InlineTable(table) => table
+            .iter()
+            .map(|(key, value)| (key.to_string(), toml_value_to_json(value)))
+            .collect(),
+    }
+}
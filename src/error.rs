@@ -0,0 +1,230 @@
+use cargo_metadata::camino::Utf8PathBuf;
+use std::{error::Error, fmt, io::ErrorKind, path::PathBuf};
+
+/// Errors raised while analyzing or updating a workspace
+#[derive(Debug)]
+pub enum AppError {
+    Metadata(String),
+    Io(std::io::Error, PathBuf),
+    TomlParse(Box<toml_edit::TomlError>, PathBuf, Option<(usize, usize)>),
+    WorkspaceUpdate(String),
+    MemberUpdate(String, Utf8PathBuf),
+    DependencyUpdate(String, String, String, Utf8PathBuf),
+    RegistryConflict(String, String),
+    MemberFailures(Vec<AppError>),
+    Locked(PathBuf),
+    WorkspaceFailures(Vec<AppError>),
+    InvalidOptions(String),
+    NotAWorkspace(Utf8PathBuf),
+    Aborted,
+    VersionConflict(String, String),
+    NotWorkspaceRoot(PathBuf, Utf8PathBuf),
+    UnusedWorkspaceDependencies(Vec<String>),
+    TooManyMembers(usize, usize),
+    Git(String),
+    ReadOnly(PathBuf),
+    ManifestNotFound(PathBuf),
+    ManifestNotUtf8(PathBuf),
+    Unsorted(PathBuf),
+    CargoTooOld(String),
+    LockfileMissing(Utf8PathBuf),
+    LockedVersionConflict(String, String),
+    Fragmented(Vec<(String, f64)>, f64),
+    RootSpelling(Vec<(String, String)>),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Metadata(msg) => write!(f, "Failed to retrieve cargo metadata: {}", msg),
+            AppError::Io(err, path) => write!(f, "IO error at '{}': {}", path.display(), err),
+            AppError::TomlParse(err, path, Some((line, column))) => write!(
+                f,
+                "TOML parse error in '{}:{}:{}': {}",
+                path.display(),
+                line,
+                column,
+                err.message().trim_end()
+            ),
+            AppError::TomlParse(err, path, None) => {
+                write!(f, "TOML parse error in '{}': {}", path.display(), err)
+            }
+            AppError::WorkspaceUpdate(msg) => {
+                write!(f, "Failed to update workspace Cargo.toml: {}", msg)
+            }
+            AppError::MemberUpdate(msg, path) => write!(
+                f,
+                "Failed to update member Cargo.toml at '{}': {}",
+                path, msg
+            ),
+            AppError::DependencyUpdate(name, table_name, msg, path) => write!(
+                f,
+                "Failed to update dependency '{}' in [{}] of '{}': {}",
+                name, table_name, path, msg
+            ),
+            AppError::RegistryConflict(name, registries) => write!(
+                f,
+                "Dependency '{}' is used from multiple registries ({}) and cannot be hoisted",
+                name, registries
+            ),
+            AppError::MemberFailures(errors) => {
+                write!(
+                    f,
+                    "Failed to update {} member Cargo.toml files:",
+                    errors.len()
+                )?;
+                for err in errors {
+                    write!(f, "\n  - {}", err)?;
+                }
+                Ok(())
+            }
+            AppError::Locked(path) => write!(
+                f,
+                "the manifest {} needs to be updated but --locked was passed to prevent this",
+                path.display()
+            ),
+            AppError::WorkspaceFailures(errors) => {
+                write!(f, "Failed to process {} workspaces:", errors.len())?;
+                for err in errors {
+                    write!(f, "\n  - {}", err)?;
+                }
+                Ok(())
+            }
+            AppError::InvalidOptions(msg) => write!(f, "Invalid options: {}", msg),
+            AppError::NotAWorkspace(path) => write!(
+                f,
+                "'{}' is not a workspace; nothing to do (pass --force --init-workspace-section to hoist anyway)",
+                path
+            ),
+            AppError::Aborted => write!(f, "Aborted, no manifest was updated"),
+            AppError::VersionConflict(name, requirements) => write!(
+                f,
+                "Dependency '{}' is required with different versions ({}) across members",
+                name, requirements
+            ),
+            AppError::NotWorkspaceRoot(path, root) => write!(
+                f,
+                "'{}' is not a workspace root, it belongs to the workspace at '{}'",
+                path.display(),
+                root
+            ),
+            AppError::UnusedWorkspaceDependencies(names) => write!(
+                f,
+                "workspace dependencies not inherited by any member: {}",
+                names.join(", ")
+            ),
+            AppError::TooManyMembers(members, max) => write!(
+                f,
+                "the workspace has {} members, more than --max-members {} (pass --force to update them anyway)",
+                members, max
+            ),
+            AppError::Git(msg) => write!(f, "Failed to list the changed members: {}", msg),
+            AppError::ReadOnly(path) => write!(
+                f,
+                "Cannot write to '{}': permission denied or read-only filesystem",
+                path.display()
+            ),
+            AppError::ManifestNotFound(path) => write!(
+                f,
+                "No manifest at '{}' (pass --workspace-root or --manifest-path to point at the workspace)",
+                path.display()
+            ),
+            AppError::CargoTooOld(version) => write!(
+                f,
+                "cargo {} is too old: workspace.dependencies requires cargo 1.64 or newer (update it, e.g. with `rustup update`)",
+                version
+            ),
+            AppError::Unsorted(path) => write!(
+                f,
+                "the workspace.dependencies of {} are not sorted but --locked was passed to prevent sorting them",
+                path.display()
+            ),
+            AppError::LockfileMissing(path) => write!(
+                f,
+                "--version-source lockfile needs '{}' (run `cargo generate-lockfile` first)",
+                path
+            ),
+            AppError::LockedVersionConflict(name, versions) => write!(
+                f,
+                "Dependency '{}' is locked at versions ({}) none of which every member accepts",
+                name, versions
+            ),
+            AppError::Fragmented(deps, max) => write!(
+                f,
+                "dependencies more fragmented than --max-fragmentation {}: {}",
+                max,
+                deps.iter()
+                    .map(|(name, fragmentation)| format!("{} ({:.2})", name, fragmentation))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            AppError::RootSpelling(mismatches) => write!(
+                f,
+                "workspace.dependencies spells crates differently from the members: {} (pass --fix-root-spelling to rename the entries)",
+                mismatches
+                    .iter()
+                    .map(|(key, name)| format!("'{}' for '{}'", key, name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            AppError::ManifestNotUtf8(path) => write!(
+                f,
+                "'{}' is not valid UTF-8, cargo only reads UTF-8 manifests",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for AppError {}
+
+impl From<cargo_metadata::Error> for AppError {
+    fn from(err: cargo_metadata::Error) -> Self {
+        AppError::Metadata(err.to_string())
+    }
+}
+
+pub(crate) fn io_err(err: std::io::Error, path: impl Into<PathBuf>) -> AppError {
+    AppError::Io(err, path.into())
+}
+
+/// Like `io_err` for writes, telling permission problems apart from other failures
+pub(crate) fn write_err(err: std::io::Error, path: impl Into<PathBuf>) -> AppError {
+    match err.kind() {
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => {
+            AppError::ReadOnly(path.into())
+        }
+        _ => AppError::Io(err, path.into()),
+    }
+}
+
+/// Like `io_err` for manifest reads, telling a missing manifest and one that isn't UTF-8 apart
+pub(crate) fn read_err(err: std::io::Error, path: impl Into<PathBuf>) -> AppError {
+    match err.kind() {
+        ErrorKind::NotFound => AppError::ManifestNotFound(path.into()),
+        ErrorKind::InvalidData => AppError::ManifestNotUtf8(path.into()),
+        _ => AppError::Io(err, path.into()),
+    }
+}
+
+/// Like `io_err` for parse errors, locating them by line and column in the parsed `content`
+pub(crate) fn toml_err(
+    err: toml_edit::TomlError,
+    path: impl Into<PathBuf>,
+    content: &str,
+) -> AppError {
+    let location = err
+        .span()
+        .and_then(|span| content.get(..span.start))
+        .map(|before| {
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        });
+    AppError::TomlParse(Box::new(err), path.into(), location)
+}
+
+/// Result type used throughout the crate
+pub type AppResult<T> = Result<T, AppError>;
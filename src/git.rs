@@ -0,0 +1,45 @@
+use crate::{AppError, AppResult};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::debug;
+
+/// The absolute paths of the files changed since the git `since` ref in the repository
+/// containing `dir`, uncommitted changes included
+pub(crate) fn changed_paths(dir: &Path, since: &str) -> AppResult<Vec<PathBuf>> {
+    // Never let the ref be taken for an option
+    if since.starts_with('-') {
+        return Err(AppError::Git(format!("invalid git ref '{}'", since)));
+    }
+    let toplevel = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = PathBuf::from(toplevel.trim_end());
+    let changed = git(dir, &["diff", "--name-only", since, "--"])?;
+    let paths: Vec<PathBuf> = changed.lines().map(|path| toplevel.join(path)).collect();
+    debug!("{} files changed since {}", paths.len(), since);
+    Ok(paths)
+}
+
+/// The root of the git work tree containing `dir`, if it's in one
+pub(crate) fn toplevel(dir: &Path) -> Option<PathBuf> {
+    let toplevel = git(dir, &["rev-parse", "--show-toplevel"]).ok()?;
+    fs::canonicalize(toplevel.trim_end()).ok()
+}
+
+fn git(dir: &Path, args: &[&str]) -> AppResult<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|e| AppError::Git(format!("failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(AppError::Git(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| AppError::Git(format!("git {} printed non UTF-8 paths", args.join(" "))))
+}
@@ -0,0 +1,3271 @@
+#![doc = include_str!("../README.md")]
+
+mod config;
+mod discovery;
+mod error;
+mod git;
+mod lockfile;
+mod patch;
+mod trace;
+
+pub use config::{
+    Config, ConflictPolicy, FeaturesStrategy, OutputFormat, TableStyle, VersionSource,
+};
+pub use error::{AppError, AppResult};
+pub use lockfile::{LockChange, lock_changes};
+
+use cargo_metadata::{
+    Dependency, DependencyKind, Metadata, MetadataCommand, Package, PackageId,
+    camino::{Utf8Path, Utf8PathBuf},
+    semver::{Op, VersionReq},
+};
+use colored::Colorize;
+use config::normalize_kind;
+use error::{io_err, read_err, toml_err, write_err};
+use patch::SharedPatch;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt, fs,
+    io::{self, Write},
+    ops::AddAssign,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use toml_edit::{self, DocumentMut};
+use tracing::{debug, error, info, subscriber::NoSubscriber, trace, warn};
+
+/// Dependency tables of a manifest along with the kind of dependencies they declare
+const DEPENDENCY_TABLES: [(&str, DependencyKind); 3] = [
+    ("dependencies", DependencyKind::Normal),
+    ("dev-dependencies", DependencyKind::Development),
+    ("build-dependencies", DependencyKind::Build),
+];
+
+/// Manifests parsed and bytes written by this process, reported per workspace in `RunStats`
+static FILES_PARSED: AtomicUsize = AtomicUsize::new(0);
+static BYTES_WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+fn dependency_table_name(kind: DependencyKind) -> &'static str {
+    DEPENDENCY_TABLES
+        .iter()
+        .find(|(_, table_kind)| *table_kind == kind)
+        .map_or("dependencies", |(table_name, _)| table_name)
+}
+
+/// Counts reported for a processed workspace
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    /// Number of workspace members
+    pub members: usize,
+    /// Number of member Cargo.toml files updated
+    pub members_updated: usize,
+    /// Number of common dependencies hoisted
+    pub common_deps: usize,
+    /// Whether the root Cargo.toml was updated
+    pub root_modified: bool,
+    /// Where the time went
+    pub stats: RunStats,
+    /// Why each dependency was or wasn't hoisted, per workspace, with `explain`
+    pub explanations: Vec<Explanation>,
+}
+
+/// The `explain` decisions taken for the dependencies of a workspace
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    /// Root of the workspace
+    pub workspace_root: Utf8PathBuf,
+    /// Decisions sorted by dependency name
+    pub decisions: Vec<Decision>,
+}
+
+/// Whether a dependency seen across the workspace members was hoisted, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    /// Name of the dependency
+    pub name: String,
+    /// `hoisted` or `skipped`
+    pub decision: &'static str,
+    /// Why, e.g. `shared` or `below-threshold`
+    pub reason: &'static str,
+    /// Members declaring the dependency
+    pub members: Vec<String>,
+    /// Number of counted occurrences, for the decisions that depend on it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occurrences: Option<usize>,
+    /// Number of occurrences required, for dependencies below it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<u32>,
+    /// The decision in words, e.g. `skipped (path dependency)`
+    #[serde(skip)]
+    pub description: String,
+}
+
+/// Time spent in each phase of a run, along with the files it read and wrote
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RunStats {
+    /// Running `cargo metadata` (or reading its cache)
+    pub metadata: Duration,
+    /// Detecting the common dependencies
+    pub analysis: Duration,
+    /// Updating the root Cargo.toml
+    pub root_update: Duration,
+    /// Updating the member Cargo.toml files
+    pub member_updates: Duration,
+    /// Number of manifests parsed
+    pub files_parsed: usize,
+    /// Number of bytes written to manifests
+    pub bytes_written: usize,
+}
+
+impl AddAssign for RunStats {
+    fn add_assign(&mut self, other: Self) {
+        self.metadata += other.metadata;
+        self.analysis += other.analysis;
+        self.root_update += other.root_update;
+        self.member_updates += other.member_updates;
+        self.files_parsed += other.files_parsed;
+        self.bytes_written += other.bytes_written;
+    }
+}
+
+/// Hoists the common dependencies of every configured workspace, returning the counts summed
+/// over all of them
+pub fn run(config: &Config) -> AppResult<RunSummary> {
+    let _trace = trace::start(config.trace_file.as_deref())?;
+    let quiet = config.is_quiet();
+    let workspace_roots = match (config.workspace_roots.as_slice(), &config.manifest_path) {
+        ([], None) => vec![PathBuf::from(".")],
+        ([], Some(manifest_path)) => vec![manifest_directory(manifest_path)],
+        (roots, None) => roots.to_vec(),
+        (_, Some(_)) => {
+            return Err(AppError::InvalidOptions(
+                "--manifest-path cannot be used with --workspace-root".to_string(),
+            ));
+        }
+    };
+    if config.report {
+        let mut fragmented = Vec::new();
+        for workspace_root in &workspace_roots {
+            let report = report_workspace(config, workspace_root)?;
+            if !quiet {
+                print_report(workspace_root, &report, config.format);
+            }
+            fragmented.extend(
+                report
+                    .into_iter()
+                    .map(|dep| (dep.name, dep.requirements.len(), dep.fragmentation)),
+            );
+        }
+        check_fragmentation(config, fragmented)?;
+        return Ok(RunSummary::default());
+    }
+    if config.dedupe_only {
+        for workspace_root in &workspace_roots {
+            let savings = dedupe_report(config, workspace_root)?;
+            if !quiet {
+                print_dedupe_report(workspace_root, &savings, config.format);
+            }
+        }
+        return Ok(RunSummary::default());
+    }
+    if config.report_unused {
+        let mut all_unused = Vec::new();
+        for workspace_root in &workspace_roots {
+            let unused = unused_workspace_dependencies(config, workspace_root)?;
+            if !quiet {
+                print_unused(workspace_root, &unused, config.format);
+            }
+            all_unused.extend(unused);
+        }
+        if config.locked && !all_unused.is_empty() {
+            return Err(AppError::UnusedWorkspaceDependencies(all_unused));
+        }
+        return Ok(RunSummary::default());
+    }
+    if config.print_workspace_deps {
+        for workspace_root in &workspace_roots {
+            let snippet = workspace_dependencies_snippet(config, workspace_root)?;
+            if !quiet {
+                if workspace_roots.len() > 1 {
+                    println!("# {}", workspace_root.display());
+                }
+                print!("{}", snippet);
+            }
+        }
+        return Ok(RunSummary::default());
+    }
+    if config.list {
+        for workspace_root in &workspace_roots {
+            let common_deps = list_workspace(config, workspace_root)?;
+            if !quiet {
+                print_list(workspace_root, &common_deps, config.format);
+            }
+        }
+        return Ok(RunSummary::default());
+    }
+    if let Some(patch_path) = &config.emit_patch {
+        if config.output.is_some() {
+            return Err(AppError::InvalidOptions(
+                "--output cannot be used with --emit-patch".to_string(),
+            ));
+        }
+        // Start from an empty patch, each workspace appends its own changes
+        fs::File::create(patch_path).map_err(|e| io_err(e, patch_path))?;
+    }
+    if let [workspace_root] = workspace_roots.as_slice() {
+        return run_workspace(config, workspace_root);
+    }
+    if config.output.is_some() {
+        return Err(AppError::InvalidOptions(
+            "--output cannot be used with multiple workspace roots".to_string(),
+        ));
+    }
+
+    // Each workspace is analyzed and rewritten independently
+    let mut summaries = Vec::new();
+    let mut failures = Vec::new();
+    for workspace_root in &workspace_roots {
+        info!(
+            "{} {}",
+            "Processing workspace".yellow(),
+            workspace_root.display().to_string().yellow().bold()
+        );
+        match run_workspace(config, workspace_root) {
+            Ok(summary) => summaries.push((workspace_root, Ok(summary))),
+            Err(e) => {
+                error!("{}: {}", "Error".red(), e.to_string().red());
+                summaries.push((workspace_root, Err(())));
+                failures.push(e);
+            }
+        }
+    }
+
+    // JSON output must stay parseable
+    if !quiet && config.format != OutputFormat::Json {
+        println!("{}", "Workspace summary:".green());
+        for (workspace_root, summary) in &summaries {
+            match summary {
+                Ok(summary) => println!(
+                    "  - {}: {} common dependencies, root {}, {}/{} member Cargo.toml files updated",
+                    workspace_root.display(),
+                    summary.common_deps,
+                    if summary.root_modified {
+                        "updated"
+                    } else {
+                        "unchanged"
+                    },
+                    summary.members_updated,
+                    summary.members,
+                ),
+                Err(()) => println!("  - {}: {}", workspace_root.display(), "failed".red()),
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(AppError::WorkspaceFailures(failures));
+    }
+    let mut total = RunSummary::default();
+    for summary in summaries
+        .into_iter()
+        .filter_map(|(_, summary)| summary.ok())
+    {
+        total.members += summary.members;
+        total.members_updated += summary.members_updated;
+        total.common_deps += summary.common_deps;
+        total.root_modified |= summary.root_modified;
+        total.stats += summary.stats;
+        total.explanations.extend(summary.explanations);
+    }
+    Ok(total)
+}
+
+/// The directory of the `manifest_path` file, `.` for a bare `Cargo.toml`
+fn manifest_directory(manifest_path: &Path) -> PathBuf {
+    match manifest_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// How widely a dependency is duplicated across the workspace members
+#[derive(Debug)]
+pub struct DependencyReport {
+    /// Name of the dependency
+    pub name: String,
+    /// Number of member occurrences, already inherited ones included
+    pub occurrences: usize,
+    /// Distinct version requirements the members use
+    pub requirements: BTreeSet<String>,
+    /// Distinct requirements per member using the dependency, 1 when each has its own
+    pub fragmentation: f64,
+}
+
+/// Ranks the dependencies of the workspace at `workspace_path` by how much hoisting them would
+/// help, without modifying anything
+pub fn report_workspace(
+    config: &Config,
+    workspace_path: &Path,
+) -> AppResult<Vec<DependencyReport>> {
+    let metadata = load_metadata(config, workspace_path)?;
+
+    let mut reports: HashMap<&str, DependencyReport> = HashMap::new();
+    let mut members: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for package in metadata.workspace_packages() {
+        if config.is_peripheral(&metadata.workspace_root, &package.manifest_path) {
+            continue;
+        }
+        for dep in &package.dependencies {
+            if !config.includes_kind(dep.kind)
+                || config.excludes(&dep.name, dep.kind)
+                || dep.path.is_some()
+            {
+                continue;
+            }
+            let report = reports
+                .entry(dep.name.as_str())
+                .or_insert_with(|| DependencyReport {
+                    name: dep.name.clone(),
+                    occurrences: 0,
+                    requirements: BTreeSet::new(),
+                    fragmentation: 0.0,
+                });
+            report.occurrences += 1;
+            report.requirements.insert(dep.req.to_string());
+            members
+                .entry(dep.name.as_str())
+                .or_default()
+                .insert(package.name.as_str());
+        }
+    }
+    for (name, report) in reports.iter_mut() {
+        report.fragmentation = report.requirements.len() as f64 / members[name].len() as f64;
+    }
+
+    // Most duplicated first, then most fragmented
+    let mut reports: Vec<DependencyReport> = reports.into_values().collect();
+    reports.sort_by(|a, b| {
+        b.occurrences
+            .cmp(&a.occurrences)
+            .then(b.requirements.len().cmp(&a.requirements.len()))
+            .then(a.name.cmp(&b.name))
+    });
+    Ok(reports)
+}
+
+fn print_report(workspace_root: &Path, report: &[DependencyReport], format: OutputFormat) {
+    match format {
+        OutputFormat::Text | OutputFormat::GithubAnnotations => {
+            println!("Dependency report for {}:", workspace_root.display());
+            for dep in report {
+                println!(
+                    "  - {}: {} occurrences, {} requirements ({}), fragmentation {:.2}",
+                    dep.name,
+                    dep.occurrences,
+                    dep.requirements.len(),
+                    dep.requirements
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    dep.fragmentation
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let dependencies: Vec<serde_json::Value> = report
+                .iter()
+                .map(|dep| {
+                    serde_json::json!({
+                        "name": dep.name,
+                        "occurrences": dep.occurrences,
+                        "requirements": dep.requirements,
+                        "fragmentation": dep.fragmentation,
+                    })
+                })
+                .collect();
+            let report = serde_json::json!({
+                "workspace_root": workspace_root,
+                "dependencies": dependencies,
+            });
+            println!("{}", report);
+        }
+    }
+}
+
+/// What the version declarations of a common dependency currently cost across the members
+#[derive(Debug)]
+pub struct DependencySavings {
+    /// Name of the dependency
+    pub name: String,
+    /// Member declarations spelling out a version, each a line
+    pub declarations: usize,
+    /// Characters spent on those declarations, e.g. 13 for `version = "1"` and 3 for a bare `"1"`
+    pub bytes: usize,
+}
+
+/// Measures the version declarations of the dependencies of the workspace at `workspace_path`
+/// that would be hoisted, most costly first, without modifying anything
+pub fn dedupe_report(config: &Config, workspace_path: &Path) -> AppResult<Vec<DependencySavings>> {
+    let metadata = load_metadata(config, workspace_path)?;
+    let common_deps = find_common_dependencies(&metadata, config)?;
+    // Members may spell a common dependency differently from the workspace
+    let names: HashMap<String, &String> = common_deps
+        .keys()
+        .map(|name| (normalized_name(name), name))
+        .collect();
+
+    let mut savings: HashMap<&String, DependencySavings> = HashMap::new();
+    for package in metadata.workspace_packages() {
+        let (_, manifest) = read_manifest(&package.manifest_path)?;
+        for dep in &package.dependencies {
+            let Some(name) = names.get(&normalized_name(&dep.name)) else {
+                continue;
+            };
+            let Some(item) = manifest_dependency(&manifest, dep) else {
+                continue;
+            };
+            let bytes = match item {
+                toml_edit::Item::Value(toml_edit::Value::String(version)) => {
+                    version.display_repr().len()
+                }
+                _ => match item.get("version") {
+                    Some(toml_edit::Item::Value(version)) => {
+                        "version = ".len() + version.to_string().trim().len()
+                    }
+                    _ => continue,
+                },
+            };
+            let entry = savings.entry(name).or_insert_with(|| DependencySavings {
+                name: name.to_string(),
+                declarations: 0,
+                bytes: 0,
+            });
+            entry.declarations += 1;
+            entry.bytes += bytes;
+        }
+    }
+
+    let mut savings: Vec<DependencySavings> = savings.into_values().collect();
+    savings.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.name.cmp(&b.name)));
+    Ok(savings)
+}
+
+fn print_dedupe_report(workspace_root: &Path, savings: &[DependencySavings], format: OutputFormat) {
+    let declarations: usize = savings.iter().map(|dep| dep.declarations).sum();
+    let bytes: usize = savings.iter().map(|dep| dep.bytes).sum();
+    match format {
+        OutputFormat::Text | OutputFormat::GithubAnnotations => {
+            println!(
+                "Duplicated version declarations in {}:",
+                workspace_root.display()
+            );
+            for dep in savings {
+                println!(
+                    "  - {}: {} declarations, {} bytes",
+                    dep.name, dep.declarations, dep.bytes
+                );
+            }
+            println!(
+                "{} declarations ({} bytes) would inherit from workspace.dependencies",
+                declarations, bytes
+            );
+        }
+        OutputFormat::Json => {
+            let dependencies: Vec<serde_json::Value> = savings
+                .iter()
+                .map(|dep| {
+                    serde_json::json!({
+                        "name": dep.name,
+                        "declarations": dep.declarations,
+                        "bytes": dep.bytes,
+                    })
+                })
+                .collect();
+            let report = serde_json::json!({
+                "workspace_root": workspace_root,
+                "dependencies": dependencies,
+                "declarations": declarations,
+                "bytes": bytes,
+            });
+            println!("{}", report);
+        }
+    }
+}
+
+/// Fails with the dependencies, as name, distinct requirements and fragmentation, more
+/// fragmented than `max_fragmentation`, worst first. Those every member requires identically
+/// never are
+fn check_fragmentation(config: &Config, deps: Vec<(String, usize, f64)>) -> AppResult<()> {
+    let Some(max_fragmentation) = config.max_fragmentation else {
+        return Ok(());
+    };
+    let mut fragmented: Vec<(String, f64)> = deps
+        .into_iter()
+        .filter(|(_, requirements, fragmentation)| {
+            *requirements > 1 && *fragmentation > max_fragmentation
+        })
+        .map(|(name, _, fragmentation)| (name, fragmentation))
+        .collect();
+    if fragmented.is_empty() {
+        return Ok(());
+    }
+    fragmented.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    Err(AppError::Fragmented(fragmented, max_fragmentation))
+}
+
+/// The root `workspace.dependencies` entries of the workspace at `workspace_path` that no member
+/// inherits, sorted by name. Members left out by `--since` or `--members-from-file` count too
+pub fn unused_workspace_dependencies(
+    config: &Config,
+    workspace_path: &Path,
+) -> AppResult<Vec<String>> {
+    let metadata = load_metadata(config, workspace_path)?;
+    let (_, doc) = read_manifest(metadata.workspace_root.join("Cargo.toml"))?;
+    let Some(deps_table) = doc
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+    else {
+        return Ok(Vec::new());
+    };
+    let deps_table = deps_table.as_table_like().ok_or_else(|| {
+        AppError::WorkspaceUpdate("'workspace.dependencies' is not a table".to_string())
+    })?;
+
+    let inherited = inherited_dependencies(&metadata)?;
+    let mut unused: Vec<String> = deps_table
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .filter(|name| !inherited.contains(name))
+        .collect();
+    unused.sort_unstable();
+    Ok(unused)
+}
+
+fn print_unused(workspace_root: &Path, unused: &[String], format: OutputFormat) {
+    match format {
+        OutputFormat::Text | OutputFormat::GithubAnnotations if unused.is_empty() => println!(
+            "Every workspace dependency of {} is inherited",
+            workspace_root.display()
+        ),
+        OutputFormat::Text | OutputFormat::GithubAnnotations => {
+            println!(
+                "Unused workspace dependencies of {}:",
+                workspace_root.display()
+            );
+            for name in unused {
+                println!("  - {}", name);
+            }
+        }
+        OutputFormat::Json => {
+            let report = serde_json::json!({
+                "workspace_root": workspace_root,
+                "unused": unused,
+            });
+            println!("{}", report);
+        }
+    }
+}
+
+/// The dependencies of the workspace at `workspace_path` that would be hoisted, sorted by name,
+/// without modifying anything
+pub fn list_workspace(config: &Config, workspace_path: &Path) -> AppResult<Vec<CommonDependency>> {
+    let metadata = load_metadata(config, workspace_path)?;
+    let mut common_deps: Vec<CommonDependency> = find_common_dependencies(&metadata, config)?
+        .into_values()
+        .collect();
+    common_deps.sort_by(|a, b| a.dep.name.cmp(&b.dep.name));
+    Ok(common_deps)
+}
+
+/// The root `workspace.dependencies` block hoisting would leave in the workspace at
+/// `workspace_path`, as TOML text to paste, without modifying anything
+pub fn workspace_dependencies_snippet(config: &Config, workspace_path: &Path) -> AppResult<String> {
+    let metadata = load_metadata(config, workspace_path)?;
+    let mut common_deps = find_common_dependencies(&metadata, config)?;
+    if config.version_source == VersionSource::Lockfile {
+        lockfile::pin_locked_versions(&metadata, &mut common_deps)?;
+    }
+    let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
+    let (_, mut doc) = read_manifest(&root_manifest_path)?;
+    hoist_into_root(
+        &mut doc,
+        &metadata,
+        &common_deps,
+        config,
+        &root_manifest_path,
+    )?;
+
+    // Only the block is printed, under a header even when its entries are all sections
+    let mut deps = doc["workspace"]["dependencies"].clone();
+    if let Some(deps) = deps.as_table_mut()
+        && deps.is_empty()
+    {
+        deps.set_implicit(false);
+    }
+    let mut workspace = toml_edit::Table::new();
+    workspace.set_implicit(true);
+    workspace.insert("dependencies", deps);
+    let mut snippet = toml_edit::DocumentMut::new();
+    snippet.insert("workspace", toml_edit::Item::Table(workspace));
+    Ok(snippet.to_string().trim_start().to_string())
+}
+
+fn print_list(workspace_root: &Path, common_deps: &[CommonDependency], format: OutputFormat) {
+    match format {
+        OutputFormat::Text | OutputFormat::GithubAnnotations => {
+            println!("Common dependencies of {}:", workspace_root.display());
+            for info in common_deps {
+                println!(
+                    "  - {} = {} ({} members)",
+                    info.dep.name, info.dep.req, info.occurrences
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let dependencies: Vec<serde_json::Value> = common_deps
+                .iter()
+                .map(|info| {
+                    serde_json::json!({
+                        "name": info.dep.name,
+                        "version": info.dep.req.to_string(),
+                        "members": info.occurrences,
+                        "requirements": info
+                            .requirements
+                            .iter()
+                            .map(|(member, req)| serde_json::json!({
+                                "member": member,
+                                "version": req.to_string(),
+                            }))
+                            .collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+            let list = serde_json::json!({
+                "workspace_root": workspace_root,
+                "dependencies": dependencies,
+            });
+            println!("{}", list);
+        }
+    }
+}
+
+/// Hoists the common dependencies of the workspace at `workspace_path`
+pub fn run_workspace(config: &Config, workspace_path: &Path) -> AppResult<RunSummary> {
+    let _trace = trace::start(config.trace_file.as_deref())?;
+    let files_parsed = FILES_PARSED.load(Ordering::Relaxed);
+    let bytes_written = BYTES_WRITTEN.load(Ordering::Relaxed);
+    let mut summary = hoist_workspace(config, workspace_path)?;
+    summary.stats.files_parsed = FILES_PARSED.load(Ordering::Relaxed) - files_parsed;
+    summary.stats.bytes_written = BYTES_WRITTEN.load(Ordering::Relaxed) - bytes_written;
+    Ok(summary)
+}
+
+fn hoist_workspace(config: &Config, workspace_path: &Path) -> AppResult<RunSummary> {
+    // Get cargo metadata
+    info!("{}", "Analyzing workspace...".yellow());
+    let started = Instant::now();
+    let mut metadata = load_metadata(config, workspace_path)?;
+
+    // Concurrent runs (e.g. a hook and a manual run) would interleave their rewrites, the later
+    // one starts over from what the earlier one wrote
+    let writes_in_place = !(config.dry_run || config.root_diff || config.locked)
+        && config.emit_patch.is_none()
+        && config.output.is_none();
+    let _lock = if writes_in_place {
+        // A read-only checkout fails naming the manifests, not the lock
+        check_writable(&metadata, config)?;
+        let (lock, waited) = lock_workspace(&metadata)?;
+        if waited {
+            metadata = load_metadata(config, workspace_path)?;
+            check_writable(&metadata, config)?;
+        }
+        Some(lock)
+    } else {
+        None
+    };
+    let metadata_time = started.elapsed();
+
+    // Cargo resolves the enclosing workspace of any directory, e.g. a member's, and its root is
+    // the one updated unless `--no-recurse` forbids a parent one
+    let requested = fs::canonicalize(workspace_path).map_err(|e| io_err(e, workspace_path))?;
+    if requested != metadata.workspace_root.as_std_path() {
+        if config.no_recurse {
+            return Err(AppError::NotWorkspaceRoot(
+                workspace_path.to_path_buf(),
+                metadata.workspace_root.clone(),
+            ));
+        }
+        info!(
+            "{} {}",
+            "Using the enclosing workspace at".yellow(),
+            metadata.workspace_root.as_str().bold()
+        );
+    }
+
+    // A standalone package is its own single member, hoisting would turn it into a workspace
+    if metadata.workspace_members.len() == 1 && !config.force {
+        let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
+        let (_, doc) = read_manifest(&root_manifest_path)?;
+        if !doc.contains_key("workspace") {
+            return Err(AppError::NotAWorkspace(metadata.workspace_root.clone()));
+        }
+    }
+
+    info!(
+        "{} {} {}",
+        "Detecting common dependencies across".yellow(),
+        metadata.workspace_members.len().to_string().yellow().bold(),
+        "workspace members...".yellow(),
+    );
+    let mut summary = RunSummary {
+        members: metadata.workspace_members.len(),
+        ..Default::default()
+    };
+    summary.stats.metadata = metadata_time;
+
+    // Collect dependencies used more than the minimum occurrences
+    let started = Instant::now();
+    let (mut common_deps, explanation) = analyze_dependencies(&metadata, config)?;
+    summary.explanations.extend(explanation);
+    let patches = patch::shared_patches(&metadata, config)?;
+    summary.stats.analysis = started.elapsed();
+    if !config.apply_to_patch {
+        for patch in &patches {
+            warn!(
+                "{} '{}' in [patch.{}] is declared by {} members, pass --apply-to-patch to move it to the root",
+                "Warning:".yellow().bold(),
+                patch.name,
+                patch.source,
+                patch.occurrences
+            );
+        }
+    }
+    // Members only lose their patches when the root receives them
+    let patches = if config.apply_to_patch && config.update_root {
+        patches
+    } else {
+        Vec::new()
+    };
+    if common_deps.is_empty() {
+        info!(
+            "{}",
+            "No common dependencies found across workspace members.".yellow()
+        );
+        // Unused root entries can still be pruned, the existing ones grouped or sorted and the
+        // shared patches moved
+        if !config.prune && !config.group_by_kind && !config.keep_sorted && patches.is_empty() {
+            return Ok(summary);
+        }
+    }
+    summary.common_deps = common_deps.len();
+    if !config.update_root {
+        let started = Instant::now();
+        retain_workspace_dependencies(&metadata, &mut common_deps)?;
+        summary.stats.analysis += started.elapsed();
+    }
+
+    if config.version_source == VersionSource::Lockfile {
+        let started = Instant::now();
+        lockfile::pin_locked_versions(&metadata, &mut common_deps)?;
+        summary.stats.analysis += started.elapsed();
+    }
+
+    // Hoisting a requirement the locked version doesn't satisfy makes cargo resolve another one
+    if config.check_lock {
+        let started = Instant::now();
+        for change in lock_changes(&metadata, &common_deps)? {
+            warn!(
+                "{} hoisting '{}' as {} will change member '{}' which currently resolves to {}",
+                "Warning:".yellow().bold(),
+                change.name,
+                change.requirement,
+                change.member,
+                change.locked
+            );
+        }
+        summary.stats.analysis += started.elapsed();
+    }
+
+    // Members should settle on a version before it's centralized
+    check_fragmentation(
+        config,
+        common_deps
+            .iter()
+            .map(|(name, info)| {
+                let requirements: HashSet<String> = info
+                    .requirements
+                    .iter()
+                    .map(|(_, req)| req.to_string())
+                    .collect();
+                let members: HashSet<&str> = info
+                    .requirements
+                    .iter()
+                    .map(|(member, _)| member.as_str())
+                    .collect();
+                let fragmentation = requirements.len() as f64 / members.len().max(1) as f64;
+                (name.clone(), requirements.len(), fragmentation)
+            })
+            .collect(),
+    )?;
+
+    // Nothing has been written yet, a misdirected run stops here
+    if let Some(max_members) = config.max_members
+        && summary.members > max_members
+        && !config.force
+    {
+        return Err(AppError::TooManyMembers(summary.members, max_members));
+    }
+
+    // Preview the changes silently and ask before writing anything
+    if config.confirm && !config.dry_run {
+        let preview = Config {
+            dry_run: true,
+            format: OutputFormat::Text,
+            ..config.clone()
+        };
+        let planned = tracing::subscriber::with_default(NoSubscriber::default(), || {
+            update_workspace(
+                &metadata,
+                &common_deps,
+                &patches,
+                &preview,
+                RunSummary::default(),
+            )
+        })?;
+        let changed = usize::from(planned.root_modified) + planned.members_updated;
+        if changed > 0 && !confirm_changes(changed)? {
+            return Err(AppError::Aborted);
+        }
+    }
+
+    update_workspace(&metadata, &common_deps, &patches, config, summary)
+}
+
+/// Writes the common dependencies to the root and member manifests
+fn update_workspace(
+    metadata: &Metadata,
+    common_deps: &HashMap<String, CommonDependency>,
+    patches: &[SharedPatch],
+    config: &Config,
+    mut summary: RunSummary,
+) -> AppResult<RunSummary> {
+    // Update the root Cargo.toml
+    if config.update_root {
+        info!("{}", "Updating root Cargo.toml...".yellow());
+        let started = Instant::now();
+        summary.root_modified = update_root(metadata, common_deps, patches, config)?;
+        summary.stats.root_update = started.elapsed();
+    }
+
+    if config.output.is_some() || !config.update_members {
+        return Ok(summary);
+    }
+
+    // Update all member Cargo.toml files
+    info!("{}", "Updating member Cargo.toml files...".yellow());
+    let started = Instant::now();
+    let mut updated_count = 0;
+    let mut failures = Vec::new();
+    let mut manifests = HashSet::new();
+    // Resolved like the member manifests, so a symlink into it is caught too
+    let target_directory = fs::canonicalize(&metadata.target_directory)
+        .unwrap_or_else(|_| metadata.target_directory.clone().into());
+    for package in metadata.workspace_members.iter() {
+        let pkg = metadata
+            .packages
+            .iter()
+            .find(|p| p.id == *package)
+            .ok_or_else(|| AppError::Metadata(format!("Package not found for ID: {}", package)))?;
+
+        // Keep going on failures so the remaining members are still updated
+        let updated = member_manifest(
+            metadata,
+            &target_directory,
+            &pkg.manifest_path,
+            config,
+            &mut manifests,
+        )
+        .and_then(|manifest_path| match manifest_path {
+            Some(manifest_path) => update_member(&manifest_path, common_deps, patches, config),
+            None => Ok(false),
+        });
+        match updated {
+            Ok(true) => updated_count += 1,
+            Ok(false) => {}
+            Err(e @ AppError::Locked(_)) => return Err(e),
+            Err(e) => {
+                error!("  - {} {}", "Failed to update:".red(), pkg.manifest_path);
+                failures.push(e);
+            }
+        }
+    }
+
+    if updated_count > 0 {
+        info!(
+            "{} {} {}",
+            "Updated".green(),
+            updated_count.to_string().green().bold(),
+            "member Cargo.toml files".green()
+        );
+    }
+
+    summary.stats.member_updates = started.elapsed();
+
+    if !failures.is_empty() {
+        return Err(AppError::MemberFailures(failures));
+    }
+    summary.members_updated = updated_count;
+    Ok(summary)
+}
+
+/// The file to update for the member manifest at `path`, the target of a symlinked one, or
+/// `None` when that file was updated through another member already or, with
+/// `no_follow_symlinks`, for a symlink. A manifest in the `target_directory` is generated, e.g.
+/// by `cargo package`, and is never rewritten
+fn member_manifest(
+    metadata: &Metadata,
+    target_directory: &Path,
+    path: &Utf8Path,
+    config: &Config,
+    manifests: &mut HashSet<PathBuf>,
+) -> AppResult<Option<Utf8PathBuf>> {
+    let is_symlink = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    if is_symlink && config.no_follow_symlinks {
+        warn!(
+            "{} skipping member {}: it's a symlink",
+            "Warning:".yellow().bold(),
+            path
+        );
+        return Ok(None);
+    }
+    // Resolving fails on symlink loops rather than following them
+    let resolved = fs::canonicalize(path).map_err(|e| read_err(e, path))?;
+    if discovery::in_target_directory(&metadata.workspace_root, target_directory, &resolved) {
+        return Err(AppError::MemberUpdate(
+            format!(
+                "it lies in the target directory '{}', which only holds generated files",
+                target_directory.display()
+            ),
+            path.to_path_buf(),
+        ));
+    }
+    if !manifests.insert(resolved.clone()) {
+        debug!(
+            "Skipping {}, {} was updated already",
+            path,
+            resolved.display()
+        );
+        return Ok(None);
+    }
+    match Utf8PathBuf::from_path_buf(resolved) {
+        Ok(resolved) if is_symlink => Ok(Some(resolved)),
+        _ => Ok(Some(path.to_path_buf())),
+    }
+}
+
+/// Path of the advisory lock serializing the runs rewriting the workspace at `workspace_root`,
+/// in the temporary directory so nothing is left in the source tree
+pub fn workspace_lock_path(workspace_root: &Path) -> PathBuf {
+    // FNV-1a rather than `DefaultHasher`, whose output may change between builds of the tool
+    let hash = workspace_root
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    std::env::temp_dir().join(format!("cargo-easy-dep-{hash:016x}.lock"))
+}
+
+/// Takes the advisory lock serializing the runs rewriting the workspace, released once the
+/// returned file is dropped, along with whether another run had to finish first
+fn lock_workspace(metadata: &Metadata) -> AppResult<(fs::File, bool)> {
+    // The manifest itself can't hold the lock, rewriting it replaces the file. The lock file is
+    // never removed, a run waiting on it would otherwise end up holding a lock nobody else sees
+    let path = workspace_lock_path(metadata.workspace_root.as_std_path());
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| io_err(e, &path))?;
+    match file.try_lock() {
+        Ok(()) => Ok((file, false)),
+        Err(fs::TryLockError::WouldBlock) => {
+            info!(
+                "{}",
+                "Waiting for another cargo-easy-dep run on this workspace to finish...".yellow()
+            );
+            file.lock().map_err(|e| io_err(e, &path))?;
+            Ok((file, true))
+        }
+        Err(fs::TryLockError::Error(e)) => Err(io_err(e, &path)),
+    }
+}
+
+/// Makes sure a file can be created next to every manifest about to be rewritten, which is what
+/// `write_atomically` does
+fn check_writable(metadata: &Metadata, config: &Config) -> AppResult<()> {
+    // Each directory to probe, along with the first manifest to be written there
+    let mut dirs: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+    match config.output.as_deref() {
+        Some(path) if path == Path::new("-") => {}
+        Some(path) => {
+            dirs.insert(manifest_directory(path), path.to_path_buf());
+        }
+        None => {
+            if config.update_root {
+                dirs.insert(
+                    metadata.workspace_root.clone().into(),
+                    metadata.workspace_root.join("Cargo.toml").into(),
+                );
+            }
+            if config.update_members {
+                for package in metadata.workspace_packages() {
+                    let manifest_path = package.manifest_path.as_std_path();
+                    // A symlinked manifest is written next to its target
+                    let manifest_path = match fs::read_link(manifest_path) {
+                        Ok(_) if config.no_follow_symlinks => continue,
+                        Ok(_) => fs::canonicalize(manifest_path)
+                            .unwrap_or_else(|_| manifest_path.to_path_buf()),
+                        Err(_) => manifest_path.to_path_buf(),
+                    };
+                    if let Some(dir) = manifest_path.parent() {
+                        dirs.entry(dir.into()).or_insert(manifest_path.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    for (dir, manifest_path) in dirs {
+        let probe = dir.join(format!(".easy-dep-{}.probe", std::process::id()));
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .map_err(|e| write_err(e, &manifest_path))?;
+        let _ = fs::remove_file(&probe);
+    }
+    Ok(())
+}
+
+/// Asks on stderr whether to write the `changed` manifests, declining unless answered yes
+fn confirm_changes(changed: usize) -> AppResult<bool> {
+    eprint!(
+        "{} Cargo.toml files will be updated. Proceed? [y/N] ",
+        changed.to_string().bold()
+    );
+    io::stderr().flush().map_err(|e| io_err(e, "<stderr>"))?;
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| io_err(e, "<stdin>"))?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+/// The metadata of the workspace at `workspace_path`, without the members its root excludes, its
+/// `workspace_members` narrowed to the ones selected by `--since` and `--members-from-file`
+fn load_metadata(config: &Config, workspace_path: &Path) -> AppResult<Metadata> {
+    check_manifest(config, workspace_path)?;
+    let mut metadata = fetch_metadata(config, workspace_path)?;
+
+    // Cargo already leaves excluded directories out, but a stale cache or a glob matching
+    // them must never lead to updating them
+    let (_, root) = read_manifest(metadata.workspace_root.join("Cargo.toml"))?;
+    let excluded = discovery::excluded_paths(&metadata.workspace_root, &root["workspace"]);
+    if !excluded.is_empty() {
+        let excluded_ids: HashSet<PackageId> = metadata
+            .packages
+            .iter()
+            .filter(|package| {
+                excluded
+                    .iter()
+                    .any(|path| package.manifest_path.starts_with(path))
+            })
+            .map(|package| package.id.clone())
+            .collect();
+        metadata.workspace_members.retain(|id| {
+            let keep = !excluded_ids.contains(id);
+            if !keep {
+                debug!("Ignoring excluded workspace member {}", id);
+            }
+            keep
+        });
+        metadata
+            .packages
+            .retain(|package| !excluded_ids.contains(&package.id));
+    }
+
+    // `--since` and `--members-from-file` only narrow the members counted and rewritten, what
+    // the others inherit is read from `all_members`.
+    // Each changed file belongs to the innermost member containing it
+    if let Some(since) = &config.since {
+        let changed = git::changed_paths(metadata.workspace_root.as_std_path(), since)?;
+        let member_dirs: Vec<(&PackageId, &Utf8Path)> = metadata
+            .workspace_packages()
+            .into_iter()
+            .filter_map(|package| Some((&package.id, package.manifest_path.parent()?)))
+            .collect();
+        let touched: HashSet<PackageId> = changed
+            .iter()
+            .filter_map(|path| {
+                member_dirs
+                    .iter()
+                    .filter(|(_, dir)| path.starts_with(dir))
+                    .max_by_key(|(_, dir)| dir.as_str().len())
+                    .map(|(id, _)| (*id).clone())
+            })
+            .collect();
+        debug!("{} members changed since {}", touched.len(), since);
+        metadata.workspace_members.retain(|id| touched.contains(id));
+    }
+
+    // One package name per line, e.g. computed by another tool
+    if let Some(members_path) = &config.members_from_file {
+        let content =
+            fs::read_to_string(members_path).map_err(|e| io_err(e, members_path.as_path()))?;
+        trace::read(members_path)?;
+        let names: HashSet<&str> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        let members = metadata.workspace_packages();
+        let mut unknown: Vec<&&str> = names
+            .iter()
+            .filter(|name| !members.iter().any(|package| package.name == **name))
+            .collect();
+        unknown.sort_unstable();
+        for name in unknown {
+            warn!(
+                "{} '{}' in {} is not a workspace member",
+                "Warning:".yellow().bold(),
+                name,
+                members_path.display()
+            );
+        }
+        let selected: HashSet<PackageId> = members
+            .into_iter()
+            .filter(|package| names.contains(package.name.as_str()))
+            .map(|package| package.id.clone())
+            .collect();
+        metadata
+            .workspace_members
+            .retain(|id| selected.contains(id));
+    }
+    Ok(metadata)
+}
+
+/// Every member of the workspace, including the ones `--since` and `--members-from-file` leave
+/// out of `workspace_members`
+fn all_members(metadata: &Metadata) -> Vec<&Package> {
+    // `cargo metadata --no-deps` only lists the workspace members
+    metadata.packages.iter().collect()
+}
+
+/// Fails with a specific error when the manifest cargo reads first is missing or isn't UTF-8,
+/// which `cargo metadata` would only report in its own words
+fn check_manifest(config: &Config, workspace_path: &Path) -> AppResult<()> {
+    let manifest_path = match &config.manifest_path {
+        Some(manifest_path) => manifest_path.clone(),
+        None => std::path::absolute(workspace_path)
+            .map_err(|e| io_err(e, workspace_path))?
+            .ancestors()
+            .map(|dir| dir.join("Cargo.toml"))
+            .find(|path| path.is_file())
+            .ok_or_else(|| AppError::ManifestNotFound(workspace_path.join("Cargo.toml")))?,
+    };
+    let content = fs::read(&manifest_path).map_err(|e| read_err(e, &manifest_path))?;
+    trace::read(&manifest_path)?;
+    if std::str::from_utf8(&content).is_err() {
+        return Err(AppError::ManifestNotUtf8(manifest_path));
+    }
+    Ok(())
+}
+
+/// Runs `cargo metadata`, reusing the `--metadata-cache` file while it's fresh (see
+/// `cached_metadata`)
+fn fetch_metadata(config: &Config, workspace_path: &Path) -> AppResult<Metadata> {
+    if config.no_metadata {
+        return discovery::discover_metadata(workspace_path);
+    }
+    // The cache belongs to the workspace reached from this exact path
+    let cache_key = match &config.manifest_path {
+        Some(manifest_path) => fs::canonicalize(manifest_path),
+        None => fs::canonicalize(workspace_path),
+    }
+    .ok();
+    if let Some(cache_path) = &config.metadata_cache
+        && let Some(cache_key) = &cache_key
+        && let Some(metadata) = cached_metadata(cache_path, cache_key)
+    {
+        debug!("Using cached metadata from {}", cache_path.display());
+        return Ok(metadata);
+    }
+
+    let dir = match &config.manifest_path {
+        Some(manifest_path) => manifest_directory(manifest_path),
+        None => workspace_path.to_path_buf(),
+    };
+    check_cargo_version(&dir)?;
+
+    let mut command = MetadataCommand::new();
+    match &config.manifest_path {
+        Some(manifest_path) => command.manifest_path(manifest_path),
+        None => command.current_dir(workspace_path),
+    };
+    let metadata = command.no_deps().exec().map_err(|e| {
+        AppError::Metadata(format!(
+            "Failed to get metadata: {} (pass --no-metadata to skip members that don't parse)",
+            e
+        ))
+    })?;
+
+    if let Some(cache_path) = &config.metadata_cache {
+        // A failing cache only costs speed, so it's not worth aborting the run
+        let cache = serde_json::json!({ "key": cache_key, "metadata": metadata });
+        let written = serde_json::to_vec(&cache)
+            .map_err(io::Error::other)
+            .and_then(|json| fs::write(cache_path, json));
+        if let Err(e) = written {
+            warn!(
+                "{} {}: {}",
+                "Failed to write the metadata cache".yellow(),
+                cache_path.display(),
+                e
+            );
+        }
+    }
+    Ok(metadata)
+}
+
+/// Fails when the cargo running `cargo metadata` in `dir`, possibly picked by a toolchain file,
+/// predates `workspace.dependencies`, whose manifests it couldn't read back
+fn check_cargo_version(dir: &Path) -> AppResult<()> {
+    // `MetadataCommand` runs the same cargo
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = match std::process::Command::new(cargo)
+        .current_dir(dir)
+        .arg("--version")
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        // `cargo metadata` reports what's wrong with cargo itself
+        _ => return Ok(()),
+    };
+    // e.g. `cargo 1.85.0 (d73d2caf9 2024-12-31)`
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let Some(version) = stdout
+        .split_whitespace()
+        .nth(1)
+        .and_then(|version| cargo_metadata::semver::Version::parse(version).ok())
+    else {
+        debug!("Unrecognized cargo version '{}'", stdout.trim_end());
+        return Ok(());
+    };
+    if (version.major, version.minor) < (1, 64) {
+        return Err(AppError::CargoTooOld(version.to_string()));
+    }
+    Ok(())
+}
+
+/// The metadata cached for the workspace reached from `cache_key` (the canonical workspace or
+/// manifest path), if it's newer than every manifest and no new member matches the root globs
+fn cached_metadata(cache_path: &Path, cache_key: &Path) -> Option<Metadata> {
+    #[derive(serde::Deserialize)]
+    struct Cache {
+        key: PathBuf,
+        metadata: Metadata,
+    }
+
+    let cached_at = fs::metadata(cache_path).and_then(|m| m.modified()).ok()?;
+    let Cache { key, metadata } = serde_json::from_slice(&fs::read(cache_path).ok()?).ok()?;
+    if key != cache_key {
+        debug!("Metadata cache belongs to another workspace");
+        return None;
+    }
+
+    let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
+    let fresh = std::iter::once(root_manifest_path.as_path())
+        .chain(
+            metadata
+                .workspace_packages()
+                .iter()
+                .map(|p| p.manifest_path.as_path()),
+        )
+        .all(|manifest_path| {
+            fs::metadata(manifest_path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified <= cached_at)
+        });
+    if !fresh {
+        debug!("Metadata cache is stale");
+        return None;
+    }
+
+    // A member added under an existing glob leaves every cached manifest untouched
+    let (_, root) = read_manifest(&root_manifest_path).ok()?;
+    let member_dirs = discovery::member_dirs(&metadata.workspace_root, &root).ok()?;
+    let cached_dirs: HashSet<&Utf8Path> = metadata
+        .workspace_packages()
+        .into_iter()
+        .filter_map(|package| package.manifest_path.parent())
+        .collect();
+    if let Some(dir) = member_dirs
+        .iter()
+        .find(|dir| !cached_dirs.contains(dir.as_path()))
+    {
+        debug!("Metadata cache is missing member {}", dir);
+        return None;
+    }
+    Some(metadata)
+}
+
+/// A dependency used by enough workspace members to be hoisted into the workspace
+#[derive(Debug)]
+pub struct CommonDependency {
+    /// The first occurrence seen, whose version requirement is used for the workspace
+    pub dep: Dependency,
+    /// Name of the registry the dependency comes from, `None` for crates.io
+    pub registry: Option<String>,
+    /// Path of a local dependency also declaring a version, relative to the workspace root
+    pub path: Option<String>,
+    /// Features written to the workspace entry, picked by the `--features-strategy`
+    pub features: Vec<String>,
+    /// Whether some members use the default features and others don't, the workspace entry
+    /// then disables them and the members relying on them enable the `default` feature
+    pub mixed_default_features: bool,
+    /// Whether members already inheriting the dependency rely on default features enabled by
+    /// the existing workspace entry, which they keep explicitly once it disables them
+    pub inherited_defaults: bool,
+    /// Number of member occurrences, already inherited ones included
+    pub occurrences: usize,
+    /// Kinds the dependency is used as across the members, in `DEPENDENCY_TABLES` order
+    pub kinds: Vec<DependencyKind>,
+    /// Member and version requirement of each occurrence, in member order
+    pub requirements: Vec<(String, VersionReq)>,
+    /// Other spellings members declare the dependency under (e.g. `serde-json`), each
+    /// inheriting a workspace entry of its own that renames the crate
+    pub spellings: Vec<String>,
+}
+
+/// Why a dependency seen across workspace members was or wasn't hoisted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Hoisted(usize),
+    PathDependency,
+    BelowThreshold(usize, u32),
+    KeepVersion,
+    KindNotSelected,
+    AlreadyHoisted(usize),
+    PreStable,
+    MissingKinds,
+    Excluded,
+    ExcludedSource,
+    Optional,
+    Wildcard,
+    NotRequested,
+    VersionConflict,
+}
+
+impl Verdict {
+    /// The `reason` of the decision in `--explain --format json` output
+    fn reason(&self) -> &'static str {
+        match self {
+            Verdict::Hoisted(_) => "shared",
+            Verdict::PathDependency => "path-dependency",
+            Verdict::BelowThreshold(..) => "below-threshold",
+            Verdict::KeepVersion => "keep-version",
+            Verdict::KindNotSelected => "kind-not-selected",
+            Verdict::AlreadyHoisted(_) => "already-hoisted",
+            Verdict::PreStable => "pre-stable",
+            Verdict::MissingKinds => "missing-kinds",
+            Verdict::Excluded => "excluded",
+            Verdict::ExcludedSource => "excluded-source",
+            Verdict::Optional => "optional",
+            Verdict::Wildcard => "wildcard",
+            Verdict::NotRequested => "not-requested",
+            Verdict::VersionConflict => "version-conflict",
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Hoisted(count) => write!(f, "hoisted ({} occurrences)", count),
+            Verdict::PathDependency => write!(f, "skipped (path dependency)"),
+            Verdict::KeepVersion => write!(f, "skipped (version kept via --keep-version)"),
+            Verdict::KindNotSelected => write!(f, "skipped (dependency kind not selected)"),
+            Verdict::PreStable => write!(f, "skipped (pre-1.0 version requirement)"),
+            Verdict::VersionConflict => write!(f, "skipped (conflicting version requirements)"),
+            Verdict::NotRequested => write!(f, "skipped (not selected with --dep)"),
+            Verdict::Excluded => write!(f, "skipped (excluded for its dependency kind)"),
+            Verdict::ExcludedSource => write!(f, "skipped (excluded for its source)"),
+            Verdict::Optional => write!(f, "skipped (optional dependency)"),
+            Verdict::Wildcard => write!(f, "skipped (wildcard version requirement)"),
+            Verdict::MissingKinds => write!(f, "skipped (not used as every --require-kinds kind)"),
+            Verdict::AlreadyHoisted(count) => {
+                write!(f, "skipped (already hoisted, {} occurrences)", count)
+            }
+            Verdict::BelowThreshold(count, min) => {
+                write!(f, "skipped (below threshold, {} < {})", count, min)
+            }
+        }
+    }
+}
+
+/// The features moving to the workspace entry of a dependency enabled with each of the feature
+/// `sets` across members
+fn hoisted_features(strategy: FeaturesStrategy, sets: &[BTreeSet<&str>]) -> Vec<String> {
+    let Some((first, rest)) = sets.split_first() else {
+        return Vec::new();
+    };
+    let features: BTreeSet<&str> = match strategy {
+        FeaturesStrategy::None => BTreeSet::new(),
+        FeaturesStrategy::Intersection => {
+            rest.iter().fold(first.clone(), |shared, set| &shared & set)
+        }
+        FeaturesStrategy::Union => rest.iter().fold(first.clone(), |all, set| &all | set),
+        // Only a feature list every member spells out identically moves to the workspace
+        FeaturesStrategy::Uniform if rest.iter().all(|set| set == first) => first.clone(),
+        FeaturesStrategy::Uniform => BTreeSet::new(),
+    };
+    features.iter().map(|feature| feature.to_string()).collect()
+}
+
+/// Detects the dependencies used by enough workspace members to be hoisted
+pub fn find_common_dependencies(
+    metadata: &Metadata,
+    config: &Config,
+) -> AppResult<HashMap<String, CommonDependency>> {
+    analyze_dependencies(metadata, config).map(|(common_deps, _)| common_deps)
+}
+
+/// Like `find_common_dependencies`, along with why each dependency was or wasn't hoisted with
+/// `explain`
+fn analyze_dependencies(
+    metadata: &Metadata,
+    config: &Config,
+) -> AppResult<(HashMap<String, CommonDependency>, Option<Explanation>)> {
+    // Peripheral members consume the workspace dependencies but don't vote for them
+    let mut voters = Vec::new();
+    for package_id in &metadata.workspace_members {
+        let package = metadata
+            .packages
+            .iter()
+            .find(|p| p.id == *package_id)
+            .ok_or_else(|| {
+                AppError::Metadata(format!("Package not found for ID: {}", package_id))
+            })?;
+        if config.is_peripheral(&metadata.workspace_root, &package.manifest_path) {
+            debug!(
+                "Not counting the dependencies of peripheral member {}",
+                package.name
+            );
+            continue;
+        }
+        voters.push(package);
+    }
+    let min_occurrences = config.required_occurrences(voters.len());
+    let spellings = dependency_spellings(metadata, &voters)?;
+    let thresholds = dependency_thresholds(metadata)?;
+    // Explicitly requested dependencies are hoisted regardless of the threshold
+    let threshold = |name: &str| {
+        if config.only_deps.is_empty() && !config.all {
+            thresholds.get(name).copied().unwrap_or(min_occurrences)
+        } else {
+            1
+        }
+    };
+    // Dependencies from different registries are distinct crates, so the registry is part of
+    // the identity used for counting.
+    let mut dep_count: HashMap<(String, Option<String>), usize> = HashMap::new();
+    // Names members give the registry index URLs cargo metadata reports, for the messages
+    let mut registry_names: HashMap<String, String> = HashMap::new();
+    let mut dep_info: HashMap<String, CommonDependency> = HashMap::new();
+    // The first occurrence of each dependency not common yet
+    let mut first_seen: HashMap<(String, Option<String>), CommonDependency> = HashMap::new();
+    let mut skipped: HashMap<String, Verdict> = HashMap::new();
+    // Features of each occurrence, for the `--features-strategy`
+    let mut feature_sets: HashMap<String, Vec<BTreeSet<&str>>> = HashMap::new();
+    // Occurrences already inheriting from the workspace with `workspace = true`
+    let mut inherited_count: HashMap<String, usize> = HashMap::new();
+    // An inheriting occurrence, resolved by cargo from the existing workspace entry
+    let mut inherited_deps: HashMap<String, Dependency> = HashMap::new();
+    // Kinds each dependency is used as across the workspace
+    let mut dep_kinds: HashMap<String, HashSet<DependencyKind>> = HashMap::new();
+    // Whether each dependency is used with and/or without its default features
+    let mut default_features: HashMap<String, BTreeSet<bool>> = HashMap::new();
+    // Dependencies inherited by members relying on defaults enabled by the workspace entry
+    let mut inherited_defaults: HashSet<String> = HashSet::new();
+    // Distinct version requirements of each dependency across members
+    let mut requirements: HashMap<String, BTreeSet<String>> = HashMap::new();
+    // The requirement of each occurrence along with the member declaring it
+    let mut member_requirements: HashMap<String, Vec<(String, VersionReq)>> = HashMap::new();
+    // Members declaring each dependency, whether or not it's counted, for `--explain`
+    let mut dep_members: HashMap<String, BTreeSet<String>> = HashMap::new();
+    // Spellings of each dependency other than the one it's counted as
+    let mut dep_spellings: HashMap<String, BTreeSet<String>> = HashMap::new();
+
+    // Count occurrences of each dependency and collect their info
+    for package in &voters {
+        if config.warn_duplicates {
+            warn_duplicate_dependencies(package);
+        }
+        let manifest = match read_manifest(&package.manifest_path) {
+            Ok((_, manifest)) => manifest,
+            // The other members are still counted, updating this one reports the error
+            Err(e) => {
+                warn!(
+                    "{} not counting member {}: {}",
+                    "Warning:".yellow().bold(),
+                    package.manifest_path,
+                    e
+                );
+                continue;
+            }
+        };
+
+        for dep in package.dependencies.iter() {
+            let name = spellings.get(&dep.name).unwrap_or(&dep.name);
+            dep_members
+                .entry(name.clone())
+                .or_default()
+                .insert(package.name.to_string());
+            if dep.name != *name {
+                dep_spellings
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(dep.name.clone());
+            }
+            if !config.includes_kind(dep.kind) {
+                skipped
+                    .entry(name.clone())
+                    .or_insert(Verdict::KindNotSelected);
+                continue;
+            }
+            if !config.only_deps.is_empty() && !config.only_deps.contains(name) {
+                skipped.entry(name.clone()).or_insert(Verdict::NotRequested);
+                continue;
+            }
+            if config.excludes(name, dep.kind) {
+                skipped.entry(name.clone()).or_insert(Verdict::Excluded);
+                continue;
+            }
+            if manifest_dependency(&manifest, dep).is_some_and(|item| excludes_source(config, item))
+            {
+                skipped
+                    .entry(name.clone())
+                    .or_insert(Verdict::ExcludedSource);
+                continue;
+            }
+            if config.exclude_optional && dep.optional {
+                skipped.entry(name.clone()).or_insert(Verdict::Optional);
+                continue;
+            }
+            // A path dependency is only hoistable for the version it's published with
+            if dep.path.is_some() && !(config.hoist_path_versions && dep.req != VersionReq::STAR) {
+                skipped.insert(name.clone(), Verdict::PathDependency);
+                continue;
+            }
+            if config.keep_version.contains(name) {
+                skipped.insert(name.clone(), Verdict::KeepVersion);
+                continue;
+            }
+            let count = dep_count
+                .entry((name.clone(), dep.registry.clone()))
+                .or_insert(0);
+            *count += 1;
+            if let Some(url) = &dep.registry
+                && let Some(registry) = manifest_dependency(&manifest, dep)
+                    .and_then(|item| item.get("registry"))
+                    .and_then(|registry| registry.as_str())
+            {
+                registry_names.insert(url.clone(), registry.to_string());
+            }
+            dep_kinds
+                .entry(name.clone())
+                .or_default()
+                .insert(normalize_kind(dep.kind));
+            default_features
+                .entry(name.clone())
+                .or_default()
+                .insert(dep.uses_default_features);
+            requirements
+                .entry(name.clone())
+                .or_default()
+                .insert(dep.req.to_string());
+            member_requirements
+                .entry(name.clone())
+                .or_default()
+                .push((package.name.to_string(), dep.req.clone()));
+            if manifest_dependency(&manifest, dep).is_some_and(is_inherited) {
+                *inherited_count.entry(name.clone()).or_insert(0) += 1;
+                inherited_deps
+                    .entry(name.clone())
+                    .or_insert_with(|| respelled(dep, name));
+                if dep.uses_default_features {
+                    inherited_defaults.insert(name.clone());
+                }
+            }
+            if config.features_strategy != FeaturesStrategy::None {
+                feature_sets
+                    .entry(name.clone())
+                    .or_default()
+                    .push(dep.features.iter().map(String::as_str).collect());
+            }
+            // The first occurrence is the one hoisted, its version requirement included
+            let key = (name.clone(), dep.registry.clone());
+            if !first_seen.contains_key(&key) && !dep_info.contains_key(name) {
+                // Cargo metadata only reports the registry index URL, so the registry name is
+                // looked up in the member manifest
+                let registry = manifest_dependency(&manifest, dep)
+                    .and_then(|item| item.get("registry"))
+                    .and_then(|registry| registry.as_str())
+                    .map(str::to_string);
+                let path = dep.path.as_ref().map(|path| {
+                    relative_path(metadata.workspace_root.as_std_path(), path.as_std_path())
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                });
+                first_seen.insert(
+                    key.clone(),
+                    CommonDependency {
+                        dep: respelled(dep, name),
+                        registry,
+                        path,
+                        features: Vec::new(),
+                        mixed_default_features: false,
+                        inherited_defaults: false,
+                        occurrences: 0,
+                        kinds: Vec::new(),
+                        requirements: Vec::new(),
+                        spellings: Vec::new(),
+                    },
+                );
+            }
+            if *count >= threshold(name) as usize
+                && !dep_info.contains_key(name)
+                && let Some(info) = first_seen.remove(&key)
+            {
+                dep_info.insert(name.clone(), info);
+            }
+        }
+    }
+
+    // The workspace entry members already inherit is the real requirement, not the first raw
+    // declaration seen
+    for (name, info) in dep_info.iter_mut() {
+        if let Some(dep) = inherited_deps.remove(name) {
+            info.dep = dep;
+        }
+        if let Some(spellings) = dep_spellings.remove(name) {
+            info.spellings = spellings.into_iter().collect();
+        }
+    }
+
+    let mut total_count: HashMap<&str, usize> = HashMap::new();
+    for ((name, _), count) in &dep_count {
+        *total_count.entry(name).or_insert(0) += count;
+    }
+
+    // Common dependencies left out by policy
+    let mut rejected: HashMap<String, Verdict> = HashMap::new();
+    dep_info.retain(|name, info| {
+        let count = total_count[name.as_str()];
+        let verdict = if inherited_count.get(name) == Some(&count) {
+            // Dependencies every member already inherits from the workspace need no changes,
+            // rewriting their workspace entry would only risk altering what members rely on
+            Verdict::AlreadyHoisted(count)
+        } else if !config.allow_wildcard && is_wildcard(&info.dep) {
+            // A `*` workspace entry would be inherited by every member and break `cargo publish`
+            warn!(
+                "{} skipping '{}': its version requirement is a wildcard (pass --allow-wildcard to hoist it anyway)",
+                "Warning:".yellow().bold(),
+                name
+            );
+            Verdict::Wildcard
+        } else if config.skip_pre_1 && is_pre_stable(&info.dep.req) {
+            Verdict::PreStable
+        } else if !config.covers_required_kinds(&dep_kinds[name]) {
+            Verdict::MissingKinds
+        } else if requirements[name].len() > 1 && config.on_conflict == ConflictPolicy::Warn {
+            warn!(
+                "{} skipping '{}': members require different versions ({})",
+                "Warning:".yellow().bold(),
+                name,
+                requirements[name]
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            Verdict::VersionConflict
+        } else {
+            return true;
+        };
+        rejected.insert(name.clone(), verdict);
+        false
+    });
+
+    if config.on_conflict == ConflictPolicy::Error {
+        let mut conflicts: Vec<&String> = dep_info
+            .keys()
+            .filter(|name| requirements[*name].len() > 1)
+            .collect();
+        conflicts.sort_unstable();
+        if let Some(name) = conflicts.first() {
+            return Err(AppError::VersionConflict(
+                name.to_string(),
+                requirements[*name]
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+    }
+
+    for (name, info) in dep_info.iter_mut() {
+        if let Some(sets) = feature_sets.get(name) {
+            info.features = hoisted_features(config.features_strategy, sets);
+        }
+        info.occurrences = total_count[name.as_str()];
+        info.kinds = DEPENDENCY_TABLES
+            .iter()
+            .map(|(_, kind)| *kind)
+            .filter(|kind| dep_kinds[name].contains(kind))
+            .collect();
+        info.requirements = member_requirements.remove(name).unwrap_or_default();
+        info.mixed_default_features = default_features[name].len() > 1;
+        info.inherited_defaults = inherited_defaults.contains(name);
+        if info.mixed_default_features {
+            debug!(
+                "'{}' is used both with and without default features, members relying on them enable the `default` feature",
+                name
+            );
+        }
+    }
+
+    for (name, info) in &dep_info {
+        let mut registries: Vec<&str> = dep_count
+            .keys()
+            .filter(|(dep_name, _)| dep_name == name)
+            .map(|(_, registry)| match registry {
+                Some(url) => registry_names.get(url).map_or(url.as_str(), String::as_str),
+                None => "crates.io",
+            })
+            .collect();
+        if registries.len() > 1 {
+            registries.sort_unstable();
+            return Err(AppError::RegistryConflict(
+                info.dep.name.clone(),
+                registries.join(", "),
+            ));
+        }
+    }
+
+    let explanation = config.explain.then(|| {
+        let mut verdicts: BTreeMap<&str, Verdict> = skipped
+            .iter()
+            .map(|(name, verdict)| (name.as_str(), *verdict))
+            .collect();
+        for (name, count) in &total_count {
+            let verdict = if dep_info.contains_key(*name) {
+                Verdict::Hoisted(*count)
+            } else if let Some(verdict) = rejected.get(*name) {
+                *verdict
+            } else {
+                Verdict::BelowThreshold(*count, threshold(name))
+            };
+            verdicts.insert(name, verdict);
+        }
+
+        let decisions = verdicts
+            .into_iter()
+            .map(|(name, verdict)| Decision {
+                name: name.to_string(),
+                decision: match verdict {
+                    Verdict::Hoisted(_) => "hoisted",
+                    _ => "skipped",
+                },
+                reason: verdict.reason(),
+                members: dep_members
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect(),
+                occurrences: match verdict {
+                    Verdict::Hoisted(count)
+                    | Verdict::AlreadyHoisted(count)
+                    | Verdict::BelowThreshold(count, _) => Some(count),
+                    _ => None,
+                },
+                threshold: match verdict {
+                    Verdict::BelowThreshold(_, min) => Some(min),
+                    _ => None,
+                },
+                description: verdict.to_string(),
+            })
+            .collect();
+        Explanation {
+            workspace_root: metadata.workspace_root.clone(),
+            decisions,
+        }
+    });
+
+    if !dep_info.is_empty() {
+        info!("Found {} common dependencies:", dep_info.len());
+        for (name, info) in &dep_info {
+            match &info.registry {
+                Some(registry) => info!("  - {} = {} ({})", name, info.dep.req, registry),
+                None => info!("  - {} = {}", name, info.dep.req),
+            }
+            // Spots the member dragging the hoisted version
+            let width = info
+                .requirements
+                .iter()
+                .map(|(member, _)| member.len())
+                .max()
+                .unwrap_or(0);
+            for (member, req) in &info.requirements {
+                trace!("      {:width$}  {}", member, req, width = width);
+            }
+        }
+    }
+
+    Ok((dep_info, explanation))
+}
+
+/// The name `name` is known by on crates.io, which tells neither case nor `-` and `_` apart
+fn normalized_name(name: &str) -> String {
+    name.to_lowercase().replace('_', "-")
+}
+
+/// Maps the dependency names spelled differently from another member (e.g. `serde-json` and
+/// `serde_json`) to the spelling they're counted and inherited as, the one of the root
+/// `workspace.dependencies` entry when a member uses it or else the one most members use
+fn dependency_spellings(
+    metadata: &Metadata,
+    voters: &[&Package],
+) -> AppResult<HashMap<String, String>> {
+    let (_, root) = read_manifest(metadata.workspace_root.join("Cargo.toml"))?;
+    let root_names: Vec<String> = root
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+        .and_then(|deps| deps.as_table_like())
+        .map(|deps| deps.iter().map(|(name, _)| name.to_string()).collect())
+        .unwrap_or_default();
+
+    // Each spelling with its number of occurrences, in the order they're seen
+    let mut uses: BTreeMap<String, Vec<(&str, usize)>> = BTreeMap::new();
+    for package in voters {
+        for dep in &package.dependencies {
+            let names = uses.entry(normalized_name(&dep.name)).or_default();
+            match names.iter_mut().find(|(name, _)| *name == dep.name) {
+                Some((_, count)) => *count += 1,
+                None => names.push((&dep.name, 1)),
+            }
+        }
+    }
+
+    let mut spellings = HashMap::new();
+    for names in uses.into_values() {
+        if names.len() < 2 {
+            continue;
+        }
+        // A root spelling no member uses isn't known to be the crate's name
+        let canonical = root_names
+            .iter()
+            .find(|name| names.iter().any(|(used, _)| used == name))
+            .map(String::as_str)
+            .or_else(|| {
+                names
+                    .iter()
+                    .min_by_key(|(_, count)| std::cmp::Reverse(*count))
+                    .map(|(name, _)| *name)
+            })
+            .unwrap_or(names[0].0);
+        warn!(
+            "{} {} spell the same crate, members are counted as '{}' and keep their spelling",
+            "Warning:".yellow().bold(),
+            names
+                .iter()
+                .map(|(name, _)| format!("'{}'", name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            canonical
+        );
+        for (name, _) in &names {
+            if *name != canonical {
+                spellings.insert(name.to_string(), canonical.to_string());
+            }
+        }
+    }
+    Ok(spellings)
+}
+
+/// A copy of `dep` named after its canonical spelling
+fn respelled(dep: &Dependency, name: &str) -> Dependency {
+    let mut dep = dep.clone();
+    dep.name = name.to_string();
+    dep
+}
+
+/// Per-dependency minimum occurrences from `[workspace.metadata.easy-dep] thresholds`, taking
+/// precedence over the global threshold
+fn dependency_thresholds(metadata: &Metadata) -> AppResult<HashMap<String, u32>> {
+    let Some(thresholds) = metadata.workspace_metadata.pointer("/easy-dep/thresholds") else {
+        return Ok(HashMap::new());
+    };
+    let thresholds = thresholds.as_object().ok_or_else(|| {
+        AppError::InvalidOptions(
+            "workspace.metadata.easy-dep.thresholds must be a table".to_string(),
+        )
+    })?;
+
+    thresholds
+        .iter()
+        .map(|(name, threshold)| {
+            threshold
+                .as_u64()
+                .and_then(|threshold| u32::try_from(threshold).ok())
+                .filter(|threshold| *threshold > 0)
+                .map(|threshold| (name.clone(), threshold))
+                .ok_or_else(|| {
+                    AppError::InvalidOptions(format!(
+                        "workspace.metadata.easy-dep.thresholds.{} must be a positive integer",
+                        name
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Warns about crates a member declares more than once for the same dependency kind and target,
+/// e.g. under a rename. Declaring a crate again in a `[target.<cfg>]` table is legitimate.
+fn warn_duplicate_dependencies(package: &Package) {
+    let mut declarations: BTreeMap<(&str, String), usize> = BTreeMap::new();
+    for dep in &package.dependencies {
+        let table_name = dependency_table_name(dep.kind);
+        let table_name = match &dep.target {
+            Some(target) => format!("target.'{}'.{}", target, table_name),
+            None => table_name.to_string(),
+        };
+        *declarations
+            .entry((dep.name.as_str(), table_name))
+            .or_insert(0) += 1;
+    }
+
+    for ((name, table_name), count) in declarations {
+        if count > 1 {
+            warn!(
+                "{} member '{}' declares '{}' {} times in [{}]",
+                "Warning:".yellow().bold(),
+                package.name,
+                name,
+                count,
+                table_name
+            );
+        }
+    }
+}
+
+/// Whether a registry dependency accepts any version (`*`, or no requirement at all), git
+/// dependencies being pinned by their source instead
+fn is_wildcard(dep: &Dependency) -> bool {
+    dep.req.comparators.is_empty()
+        && !dep
+            .source
+            .as_ref()
+            .is_some_and(|source| source.starts_with("git+"))
+}
+
+/// Whether a version requirement only matches pre-1.0 versions. Requirements whose major
+/// version can't be determined (e.g. `*`) are treated as stable so they're still hoisted.
+fn is_pre_stable(req: &VersionReq) -> bool {
+    !req.comparators.is_empty() && req.comparators.iter().all(|c| c.major == 0)
+}
+
+/// The shortest requirement equivalent to `req`, writing caret requirements without the caret
+/// and their trailing zeros (`^1.0.0` becomes `1`, `^0.2.0` becomes `0.2`)
+fn shortest_requirement(req: &VersionReq) -> String {
+    if req.comparators.is_empty() {
+        return "*".to_string();
+    }
+    req.comparators
+        .iter()
+        .map(|comparator| {
+            if comparator.op != Op::Caret || !comparator.pre.is_empty() {
+                return comparator.to_string();
+            }
+            let (mut minor, mut patch) = (comparator.minor, comparator.patch);
+            // `^0.0` and `^0.0.0` match different versions, only `^0.x.0` drops its patch
+            if comparator.major > 0 || minor.is_some_and(|minor| minor > 0) {
+                if patch == Some(0) {
+                    patch = None;
+                }
+                if comparator.major > 0 && patch.is_none() && minor == Some(0) {
+                    minor = None;
+                }
+            }
+            match (minor, patch) {
+                (Some(minor), Some(patch)) => format!("{}.{}.{}", comparator.major, minor, patch),
+                (Some(minor), None) => format!("{}.{}", comparator.major, minor),
+                _ => comparator.major.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reads and parses a manifest, returning its original content along with the document
+fn read_manifest(path: impl AsRef<Path>) -> AppResult<(String, DocumentMut)> {
+    let path = path.as_ref();
+    debug!("Reading {}", path.display());
+    let content = fs::read_to_string(path).map_err(|e| read_err(e, path))?;
+    trace::read(path)?;
+    let doc = content
+        .parse::<DocumentMut>()
+        .map_err(|e| toml_err(e, path, &content))?;
+    FILES_PARSED.fetch_add(1, Ordering::Relaxed);
+    Ok((content, doc))
+}
+
+/// Finds the entry declaring `dep` in the member manifest it comes from
+fn manifest_dependency<'a>(doc: &'a DocumentMut, dep: &Dependency) -> Option<&'a toml_edit::Item> {
+    let table_name = dependency_table_name(dep.kind);
+    let deps = match &dep.target {
+        Some(target) => doc
+            .get("target")
+            .and_then(|t| t.get(target.to_string()))
+            .and_then(|t| t.get(table_name)),
+        None => doc.get(table_name),
+    };
+
+    deps.and_then(|deps| deps.get(dep.rename.as_deref().unwrap_or(&dep.name)))
+}
+
+/// Whether a member dependency entry comes from a source excluded with `exclude_git` or
+/// `exclude_registries`
+fn excludes_source(config: &Config, item: &toml_edit::Item) -> bool {
+    if item.get("git").is_some() {
+        return config.exclude_git;
+    }
+    let registry = item
+        .get("registry")
+        .and_then(|registry| registry.as_str())
+        .unwrap_or("crates-io");
+    config
+        .exclude_registries
+        .iter()
+        .any(|excluded| excluded == registry)
+}
+
+/// Whether a member dependency entry already inherits from the workspace
+fn is_inherited(item: &toml_edit::Item) -> bool {
+    item.get("workspace")
+        .and_then(|workspace| workspace.as_bool())
+        .unwrap_or(false)
+}
+
+/// Adds the common dependencies to the root `workspace.dependencies`, returning whether the
+/// manifest changed
+pub fn update_root_cargo_toml(
+    metadata: &Metadata,
+    common_deps: &HashMap<String, CommonDependency>,
+    config: &Config,
+) -> AppResult<bool> {
+    update_root(metadata, common_deps, &[], config)
+}
+
+/// Like `update_root_cargo_toml`, also adding the `patches` shared by the members
+fn update_root(
+    metadata: &Metadata,
+    common_deps: &HashMap<String, CommonDependency>,
+    patches: &[SharedPatch],
+    config: &Config,
+) -> AppResult<bool> {
+    let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
+    let (content, mut doc) = read_manifest(&root_manifest_path)?;
+
+    let mut modified =
+        hoist_into_root(&mut doc, metadata, common_deps, config, &root_manifest_path)?;
+    modified |= patch::hoist_patches(&mut doc, patches)?;
+
+    if modified {
+        annotate_change(
+            root_manifest_path.as_std_path(),
+            &content,
+            &render_manifest(&doc, &content),
+            config,
+        );
+    }
+
+    if modified && config.locked {
+        return Err(AppError::Locked(root_manifest_path.into()));
+    }
+
+    match config.output.as_deref() {
+        _ if config.root_diff => print!(
+            "{}",
+            manifest_diff(
+                root_manifest_path.as_std_path(),
+                &content,
+                &render_manifest(&doc, &content),
+                config.diff_context
+            )
+        ),
+        _ if config.dry_run => {}
+        Some(path) if path == Path::new("-") => print!("{}", render_manifest(&doc, &content)),
+        Some(path) => {
+            let rendered = render_manifest(&doc, &content);
+            let changed = fs::read_to_string(path).ok().as_deref() != Some(rendered.as_str());
+            write_atomically(path, &rendered).map_err(|e| write_err(e, path))?;
+            trace::write(path, changed)?;
+        }
+        // Rewriting an unchanged manifest would only bump its modification time
+        None if !modified => {}
+        None => write_manifest(
+            root_manifest_path.as_std_path(),
+            &content,
+            &render_manifest(&doc, &content),
+            config,
+        )?,
+    }
+
+    if modified {
+        info!(
+            "{} {} {}",
+            "Updated root Cargo.toml with".green(),
+            common_deps.len().to_string().green().bold(),
+            "common dependencies".green(),
+        );
+    } else {
+        info!("{}", "No changes needed for root Cargo.toml".green());
+    }
+
+    // Report the versions actually written, pre-existing root entries are kept as they are
+    info!("{}", "Workspace dependency versions:".green());
+    let names: BTreeSet<&String> = common_deps.keys().collect();
+    for name in names {
+        let version = doc["workspace"]["dependencies"]
+            .get(name)
+            .and_then(workspace_dependency_version);
+        match version {
+            Some(version)
+                if VersionReq::parse(version).is_ok_and(|req| {
+                    shortest_requirement(&req) == shortest_requirement(&common_deps[name].dep.req)
+                }) =>
+            {
+                info!("  - {} = {}", name, version)
+            }
+            Some(version) => info!(
+                "  - {} = {} {}",
+                name,
+                version.yellow(),
+                format!("(first seen as {})", common_deps[name].dep.req).yellow()
+            ),
+            None => info!("  - {} {}", name, "(no version)".yellow()),
+        }
+    }
+
+    Ok(modified)
+}
+
+/// Adds the common dependencies to the root `workspace.dependencies` of `doc`, pruning, grouping
+/// and sorting its entries as configured, and returns whether it changed
+fn hoist_into_root(
+    doc: &mut toml_edit::DocumentMut,
+    metadata: &Metadata,
+    common_deps: &HashMap<String, CommonDependency>,
+    config: &Config,
+    root_manifest_path: &Utf8Path,
+) -> AppResult<bool> {
+    // Only turn the root into a workspace root when asked to
+    if !doc.contains_key("workspace") {
+        if !config.init_workspace_section {
+            return Err(AppError::WorkspaceUpdate(
+                "no [workspace] table to hoist into, pass --init-workspace-section to create one"
+                    .to_string(),
+            ));
+        }
+        doc["workspace"] = toml_edit::Item::Table(toml_edit::Table::new());
+    }
+
+    // Create or get the workspace.dependencies table
+    if !doc["workspace"]
+        .as_table()
+        .ok_or_else(|| AppError::WorkspaceUpdate("'workspace' is not a table".to_string()))?
+        .contains_key("dependencies")
+    {
+        let mut deps_table = toml_edit::Table::new();
+        // Entries written as sections need no header of their own
+        deps_table.set_implicit(config.root_style() == TableStyle::Section);
+        doc["workspace"]["dependencies"] = toml_edit::Item::Table(deps_table);
+    }
+
+    let mut modified = fix_root_spellings(doc, common_deps, config)?;
+
+    // Add each common dependency to workspace.dependencies
+    for (name, info) in common_deps {
+        // Entries can also be written as `[workspace.dependencies.<name>]` sections, dotted keys
+        // or an inline table
+        let deps_table = doc["workspace"]["dependencies"]
+            .as_table_like_mut()
+            .ok_or_else(|| {
+                AppError::WorkspaceUpdate("'workspace.dependencies' is not a table".to_string())
+            })?;
+
+        // Simple version string
+        let mut dep = deps_table.entry(name).or_insert_with(|| {
+            modified = true;
+            workspace_dependency_entry(info, config)
+        });
+
+        match &mut dep {
+            toml_edit::Item::Value(toml_edit::Value::String(version)) => {
+                // Promote to a table with default-features = false, keeping the version the
+                // workspace already settled on
+                let version = version.value().clone();
+                *dep = workspace_dependency_entry(info, config);
+                dep["version"] = toml_edit::value(version);
+                modified = true;
+            }
+            toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
+                // Add default-features = false
+                let entry = table.entry("default-features").or_insert_with(|| {
+                    modified = true;
+                    toml_edit::Value::Boolean(toml_edit::Formatted::new(false))
+                });
+
+                if let Some(is_default_features) = entry.as_bool()
+                    && is_default_features
+                {
+                    *entry = toml_edit::Value::Boolean(toml_edit::Formatted::new(false));
+                    modified = true;
+                }
+
+                modified |= merge_workspace_features(table, &info.features);
+            }
+            toml_edit::Item::Table(table) => {
+                // Add default-features = false
+                let entry = table.entry("default-features").or_insert_with(|| {
+                    modified = true;
+                    toml_edit::value(false)
+                });
+
+                if let Some(is_default_features) = entry.as_bool()
+                    && is_default_features
+                {
+                    *entry = toml_edit::value(false);
+                    modified = true;
+                }
+
+                modified |= merge_workspace_features(table, &info.features);
+            }
+
+            toml_edit::Item::ArrayOfTables(tables) => {
+                for table in tables.iter_mut() {
+                    // Not a member dependency table, so no kind exclusion applies
+                    modified |= update_dependencies_table(
+                        table,
+                        "workspace.dependencies",
+                        DependencyKind::Unknown,
+                        root_manifest_path,
+                        common_deps,
+                        config,
+                    )?;
+                }
+            }
+            _ => {}
+        }
+        modified |= add_spelling_entries(deps_table, name, &info.spellings);
+    }
+
+    if config.prune {
+        modified |= prune_workspace_dependencies(doc, metadata, common_deps)?;
+    }
+
+    if config.group_by_kind {
+        modified |= group_workspace_dependencies(doc, metadata, common_deps, config.keep_sorted);
+    } else if config.keep_sorted && sort_workspace_dependencies(doc) {
+        if config.locked {
+            return Err(AppError::Unsorted(root_manifest_path.into()));
+        }
+        modified = true;
+    }
+
+    Ok(modified)
+}
+
+/// Removes the root `workspace.dependencies` entries no member inherits anymore, returning
+/// whether any was removed
+fn prune_workspace_dependencies(
+    doc: &mut DocumentMut,
+    metadata: &Metadata,
+    common_deps: &HashMap<String, CommonDependency>,
+) -> AppResult<bool> {
+    // The common dependencies are about to be inherited by the members
+    let mut referenced = inherited_dependencies(metadata)?;
+    referenced.extend(common_deps.keys().cloned());
+    referenced.extend(common_deps.values().flat_map(|info| info.spellings.clone()));
+
+    let deps_table = doc["workspace"]["dependencies"]
+        .as_table_like_mut()
+        .ok_or_else(|| {
+            AppError::WorkspaceUpdate("'workspace.dependencies' is not a table".to_string())
+        })?;
+    let unused: Vec<String> = deps_table
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .filter(|name| !referenced.contains(name))
+        .collect();
+    for name in &unused {
+        deps_table.remove(name);
+        info!(
+            "{} {}",
+            "Pruned unused workspace dependency".yellow(),
+            name.yellow().bold()
+        );
+    }
+
+    Ok(!unused.is_empty())
+}
+
+/// Comment headers of the `--group-by-kind` groups, in `DEPENDENCY_TABLES` order followed by
+/// the entries no member uses
+const KIND_GROUPS: [&str; 4] = ["# normal", "# dev", "# build", "# unused"];
+
+/// The `KIND_GROUPS` index of dependencies of `kind`
+fn kind_group(kind: DependencyKind) -> usize {
+    let kind = normalize_kind(kind);
+    DEPENDENCY_TABLES
+        .iter()
+        .position(|(_, table_kind)| *table_kind == kind)
+        .unwrap_or(0)
+}
+
+/// Sorts the root `workspace.dependencies` entries by the first kind they're used as, with a
+/// comment header above each group, and by name within a group when `sorted`, returning whether
+/// the manifest changed
+fn group_workspace_dependencies(
+    doc: &mut DocumentMut,
+    metadata: &Metadata,
+    common_deps: &HashMap<String, CommonDependency>,
+    sorted: bool,
+) -> bool {
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    let mut add = |name: String, group: usize| {
+        groups
+            .entry(name)
+            .and_modify(|existing| *existing = (*existing).min(group))
+            .or_insert(group);
+    };
+    for package in all_members(metadata) {
+        for dep in &package.dependencies {
+            add(
+                dep.rename.clone().unwrap_or_else(|| dep.name.clone()),
+                kind_group(dep.kind),
+            );
+        }
+    }
+    for (name, info) in common_deps {
+        if let Some(kind) = info.kinds.first() {
+            add(name.clone(), kind_group(*kind));
+        }
+    }
+    let group_of = |name: &str| groups.get(name).copied().unwrap_or(KIND_GROUPS.len() - 1);
+
+    let Some(deps_table) = doc["workspace"]["dependencies"].as_table_mut() else {
+        // Comments can't be written inside an inline table
+        debug!("Not grouping the inline workspace.dependencies table");
+        return false;
+    };
+    let before = deps_table.to_string();
+
+    // The sort is stable, so entries otherwise keep their order within a group
+    deps_table.sort_values_by(|a, _, b, _| {
+        let order = group_of(a.get()).cmp(&group_of(b.get()));
+        if sorted {
+            order.then_with(|| a.get().cmp(b.get()))
+        } else {
+            order
+        }
+    });
+
+    let names: Vec<String> = deps_table
+        .iter()
+        .filter(|(_, item)| item.is_value())
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let mut previous = None;
+    for name in &names {
+        let group = group_of(name);
+        let Some(mut key) = deps_table.key_mut(name) else {
+            continue;
+        };
+        let decor = key.leaf_decor_mut();
+        // Headers and spacing from a previous run are rewritten rather than duplicated
+        let prefix: String = decor
+            .prefix()
+            .and_then(|prefix| prefix.as_str())
+            .unwrap_or("")
+            .split_inclusive('\n')
+            .filter(|line| {
+                let comment = line.trim();
+                !KIND_GROUPS.contains(&comment) && (!comment.is_empty() || !line.ends_with('\n'))
+            })
+            .collect();
+        let prefix = match previous {
+            Some(previous) if previous == group => prefix,
+            Some(_) => format!("\n{}\n{}", KIND_GROUPS[group], prefix),
+            None => format!("{}\n{}", KIND_GROUPS[group], prefix),
+        };
+        decor.set_prefix(prefix);
+        previous = Some(group);
+    }
+
+    deps_table.to_string() != before
+}
+
+/// Sorts the root `workspace.dependencies` entries by name, returning whether their order
+/// changed. Entries written as `[workspace.dependencies.<name>]` sections keep their place
+fn sort_workspace_dependencies(doc: &mut DocumentMut) -> bool {
+    let Some(deps_table) = doc
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("dependencies"))
+        .and_then(|deps| deps.as_table_like_mut())
+    else {
+        return false;
+    };
+    let order = |deps_table: &dyn toml_edit::TableLike| -> Vec<String> {
+        deps_table
+            .iter()
+            .filter(|(_, item)| item.is_value())
+            .map(|(name, _)| name.to_string())
+            .collect()
+    };
+    let before = order(deps_table);
+    deps_table.sort_values();
+    order(deps_table) != before
+}
+
+/// Names of the dependencies any member, selected or not, already declares with `workspace = true`
+fn inherited_dependencies(metadata: &Metadata) -> AppResult<HashSet<String>> {
+    let mut inherited = HashSet::new();
+    for package in all_members(metadata) {
+        // Read from the manifest itself, as discovered metadata leaves some entries out (e.g.
+        // target-specific ones)
+        let (_, manifest) = read_manifest(&package.manifest_path)?;
+        let targets = manifest
+            .get("target")
+            .and_then(|targets| targets.as_table_like())
+            .into_iter()
+            .flat_map(|targets| {
+                targets
+                    .iter()
+                    .filter_map(|(_, target)| target.as_table_like())
+            });
+        for table in
+            std::iter::once(manifest.as_table() as &dyn toml_edit::TableLike).chain(targets)
+        {
+            for (table_name, _) in DEPENDENCY_TABLES {
+                let Some(deps) = table.get(table_name).and_then(|deps| deps.as_table_like()) else {
+                    continue;
+                };
+                inherited.extend(
+                    deps.iter()
+                        .filter(|(_, item)| is_inherited(item))
+                        .map(|(name, _)| name.to_string()),
+                );
+            }
+        }
+    }
+    Ok(inherited)
+}
+
+/// Drops common dependencies the root `workspace.dependencies` doesn't declare yet, so members
+/// are only pointed at existing workspace entries when the root is left untouched
+fn retain_workspace_dependencies(
+    metadata: &Metadata,
+    common_deps: &mut HashMap<String, CommonDependency>,
+) -> AppResult<()> {
+    let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
+    let (_, doc) = read_manifest(&root_manifest_path)?;
+
+    let workspace_deps = doc
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+        .and_then(|deps| deps.as_table_like());
+    let mut missing: Vec<String> = common_deps
+        .keys()
+        .filter(|name| !workspace_deps.is_some_and(|deps| deps.contains_key(name)))
+        .cloned()
+        .collect();
+    missing.sort_unstable();
+
+    for name in &missing {
+        common_deps.remove(name);
+    }
+    if !missing.is_empty() {
+        warn!(
+            "{} skipping dependencies missing from the root workspace.dependencies: {}",
+            "Warning:".yellow().bold(),
+            missing.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// The version requirement of a `workspace.dependencies` entry, in either string or table form
+fn workspace_dependency_version(item: &toml_edit::Item) -> Option<&str> {
+    match item {
+        toml_edit::Item::Value(toml_edit::Value::String(version)) => Some(version.value()),
+        item => item.get("version").and_then(|version| version.as_str()),
+    }
+}
+
+/// Adds the hoisted features an existing `workspace.dependencies` entry doesn't enable yet,
+/// returning whether any was added
+fn merge_workspace_features(table: &mut dyn toml_edit::TableLike, features: &[String]) -> bool {
+    if features.is_empty() {
+        return false;
+    }
+    let Some(existing) = table.get_mut("features") else {
+        table.insert(
+            "features",
+            toml_edit::value(toml_edit::Array::from_iter(features)),
+        );
+        return true;
+    };
+    let Some(existing) = existing.as_array_mut() else {
+        return false;
+    };
+
+    let missing: Vec<&String> = features
+        .iter()
+        .filter(|feature| {
+            !existing
+                .iter()
+                .any(|f| f.as_str() == Some(feature.as_str()))
+        })
+        .collect();
+    for feature in &missing {
+        existing.push(feature.as_str());
+    }
+    !missing.is_empty()
+}
+
+/// Builds the `workspace.dependencies` entry for a common dependency
+fn workspace_dependency_entry(info: &CommonDependency, config: &Config) -> toml_edit::Item {
+    let mut dep_table = toml_edit::Table::new();
+    dep_table.set_implicit(true);
+    dep_table["default-features"] = toml_edit::value(false);
+    dep_table["version"] = toml_edit::value(if config.normalize_versions {
+        shortest_requirement(&info.dep.req)
+    } else {
+        info.dep.req.to_string()
+    });
+    if let Some(registry) = &info.registry {
+        dep_table["registry"] = toml_edit::value(registry);
+    }
+    if let Some(path) = &info.path {
+        dep_table["path"] = toml_edit::value(path);
+    }
+    if !info.features.is_empty() {
+        dep_table["features"] = toml_edit::value(toml_edit::Array::from_iter(&info.features));
+    }
+    if config.root_style() == TableStyle::Section {
+        dep_table.set_implicit(false);
+        dep_table.decor_mut().set_prefix("\n");
+        return toml_edit::Item::Table(dep_table);
+    }
+    dep_table.into_inline_table().into()
+}
+
+/// Adds a workspace entry under each other spelling members declare `name` under, a copy of its
+/// entry renaming the crate so cargo resolves their `workspace = true`, returning whether any was
+/// added
+fn add_spelling_entries(
+    deps_table: &mut dyn toml_edit::TableLike,
+    name: &str,
+    spellings: &[String],
+) -> bool {
+    let Some(entry) = deps_table.get(name).cloned() else {
+        return false;
+    };
+    let mut modified = false;
+    for spelling in spellings {
+        if deps_table.contains_key(spelling) {
+            continue;
+        }
+        let mut entry = entry.clone();
+        match &mut entry {
+            toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
+                table.insert("package", name.into());
+                table.fmt();
+            }
+            toml_edit::Item::Table(table) => {
+                table.insert("package", toml_edit::value(name));
+            }
+            _ => continue,
+        }
+        deps_table.insert(spelling, entry);
+        modified = true;
+    }
+    modified
+}
+
+/// Converts a member's common dependencies to `workspace = true`, returning whether the
+/// manifest changed
+pub fn update_member_cargo_toml(
+    manifest_path: &Utf8Path,
+    common_deps: &HashMap<String, CommonDependency>,
+    config: &Config,
+) -> AppResult<bool> {
+    update_member(manifest_path, common_deps, &[], config)
+}
+
+/// Like `update_member_cargo_toml`, also removing the `patches` moved to the root
+fn update_member(
+    manifest_path: &Utf8Path,
+    common_deps: &HashMap<String, CommonDependency>,
+    patches: &[SharedPatch],
+    config: &Config,
+) -> AppResult<bool> {
+    let (content, mut doc) = read_manifest(manifest_path)?;
+
+    // Crate authors can keep their member out of reach, e.g. for vendored crates
+    let opted_out = doc
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("easy-dep"))
+        .and_then(|easy_dep| easy_dep.get("skip"))
+        .and_then(|skip| skip.as_bool())
+        == Some(true);
+    if opted_out {
+        info!(
+            "  - {} {}",
+            "Skipped member opting out with package.metadata.easy-dep.skip:".yellow(),
+            manifest_path
+        );
+        return Ok(false);
+    }
+
+    let mut modified = false;
+
+    // Update the selected dependency tables
+    for (table_name, kind) in DEPENDENCY_TABLES {
+        if !config.includes_kind(kind) {
+            continue;
+        }
+        if let Some(deps) = doc.get_mut(table_name) {
+            let type_name = deps.type_name();
+            if let Some(deps_table) = deps.as_table_like_mut() {
+                modified |= update_dependencies_table(
+                    deps_table,
+                    table_name,
+                    kind,
+                    manifest_path,
+                    common_deps,
+                    config,
+                )?;
+            } else {
+                return Err(AppError::MemberUpdate(
+                    format!("'{}' is {}, expected a table", table_name, type_name),
+                    manifest_path.to_path_buf(),
+                ));
+            }
+        }
+    }
+
+    modified |= patch::remove_patches(&mut doc, patches);
+
+    if config.prune_empty_tables {
+        modified |= remove_empty_dependency_tables(&mut doc);
+    }
+
+    if modified {
+        annotate_change(
+            manifest_path.as_std_path(),
+            &content,
+            &render_manifest(&doc, &content),
+            config,
+        );
+    }
+
+    if modified && config.locked {
+        return Err(AppError::Locked(manifest_path.into()));
+    }
+
+    if modified {
+        write_manifest(
+            manifest_path.as_std_path(),
+            &content,
+            &render_manifest(&doc, &content),
+            config,
+        )?;
+        info!("  - Updated member at: {}", manifest_path);
+    } else {
+        info!("  - No changes needed for: {}", manifest_path);
+    }
+
+    Ok(modified)
+}
+
+/// Removes the dependency tables without any entry, along with the `[target.<cfg>]` tables they
+/// leave empty, returning whether any was removed
+fn remove_empty_dependency_tables(doc: &mut DocumentMut) -> bool {
+    let is_empty = |item: &toml_edit::Item| item.as_table_like().is_some_and(|t| t.is_empty());
+    let mut modified = false;
+    for (table_name, _) in DEPENDENCY_TABLES {
+        if doc.get(table_name).is_some_and(is_empty) {
+            doc.remove(table_name);
+            modified = true;
+        }
+    }
+
+    let Some(targets) = doc
+        .get_mut("target")
+        .and_then(|targets| targets.as_table_like_mut())
+    else {
+        return modified;
+    };
+    let cfgs: Vec<String> = targets.iter().map(|(cfg, _)| cfg.to_string()).collect();
+    for cfg in cfgs {
+        let Some(target) = targets
+            .get_mut(&cfg)
+            .and_then(|target| target.as_table_like_mut())
+        else {
+            continue;
+        };
+        for (table_name, _) in DEPENDENCY_TABLES {
+            if target.get(table_name).is_some_and(is_empty) {
+                target.remove(table_name);
+                modified = true;
+            }
+        }
+        if target.is_empty() {
+            targets.remove(&cfg);
+        }
+    }
+    if targets.is_empty() {
+        doc.remove("target");
+    }
+    modified
+}
+
+/// Writes the rewritten manifest in place, or appends its diff to the `--emit-patch` file
+fn write_manifest(path: &Path, original: &str, rendered: &str, config: &Config) -> AppResult<()> {
+    if config.dry_run || config.root_diff {
+        return Ok(());
+    }
+    let Some(patch_path) = &config.emit_patch else {
+        write_atomically(path, rendered).map_err(|e| write_err(e, path))?;
+        return trace::write(path, true);
+    };
+
+    let diff = manifest_diff(path, original, rendered, config.diff_context);
+    let mut patch = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(patch_path)
+        .map_err(|e| io_err(e, patch_path))?;
+    patch
+        .write_all(diff.as_bytes())
+        .map_err(|e| io_err(e, patch_path))?;
+    trace::write(patch_path, !diff.is_empty())
+}
+
+/// The unified diff of a manifest rewrite, with `context` lines around each change
+fn manifest_diff(path: &Path, original: &str, rendered: &str, context: usize) -> String {
+    // Paths relative to the repository root so the patch applies with `git apply` wherever the
+    // run started from, or else to the current directory
+    let base = path
+        .parent()
+        .and_then(git::toplevel)
+        .or_else(|| fs::canonicalize(".").ok());
+    let relative = base
+        .map(|base| relative_path(&base, path))
+        .unwrap_or_else(|| path.to_path_buf())
+        .to_string_lossy()
+        .replace('\\', "/");
+    similar::TextDiff::from_lines(original, rendered)
+        .unified_diff()
+        .context_radius(context)
+        .header(&format!("a/{}", relative), &format!("b/{}", relative))
+        .to_string()
+}
+
+/// Prints a GitHub Actions notice at the first line of a manifest rewrite, with
+/// `--format github-annotations`
+fn annotate_change(path: &Path, original: &str, rendered: &str, config: &Config) {
+    if config.format != OutputFormat::GithubAnnotations || config.is_quiet() {
+        return;
+    }
+    // Workflow commands expect paths relative to the repository, i.e. the current directory
+    let relative = fs::canonicalize(".")
+        .map(|cwd| relative_path(&cwd, path))
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .replace('\\', "/");
+    let line = original
+        .lines()
+        .zip(rendered.lines())
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| original.lines().count().min(rendered.lines().count()))
+        .min(original.lines().count().saturating_sub(1))
+        + 1;
+    let message = if config.dry_run || config.locked || config.root_diff {
+        "Dependencies can be inherited from the workspace, run `cargo easy-dep` to update this manifest"
+    } else {
+        "Updated by `cargo easy-dep` to inherit dependencies from the workspace"
+    };
+    println!(
+        "::notice file={},line={},title=cargo-easy-dep::{}",
+        escape_annotation_property(&relative),
+        line,
+        message
+    );
+}
+
+/// Escapes a workflow command property value, see
+/// <https://github.com/actions/toolkit/blob/main/packages/core/src/command.ts>
+fn escape_annotation_property(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// The path leading from the `base` directory to `path`, going up with `..` for paths outside
+/// of it (e.g. members living next to the workspace root)
+pub(crate) fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let mut base_components = base.components().peekable();
+    let mut path_components = path.components().peekable();
+    while let (Some(a), Some(b)) = (base_components.peek(), path_components.peek())
+        && a == b
+    {
+        base_components.next();
+        path_components.next();
+    }
+    base_components
+        .map(|_| std::path::Component::ParentDir)
+        .chain(path_components)
+        .collect()
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it over `path`, so an
+/// interrupted run never leaves a truncated manifest behind
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    // Renaming over a symlink would replace it rather than update its target
+    let resolved;
+    let path = if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+        resolved = fs::canonicalize(path)?;
+        &resolved
+    } else {
+        path
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".easy-dep-{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let written = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+    match written {
+        Ok(()) => {
+            BYTES_WRITTEN.fetch_add(contents.len(), Ordering::Relaxed);
+        }
+        Err(_) => {
+            let _ = fs::remove_file(&temp_path);
+        }
+    }
+    written
+}
+
+/// Renders `doc` keeping the line endings and the trailing newline (or lack of it) of the
+/// `original` manifest content, so rewrites don't churn unrelated lines
+fn render_manifest(doc: &DocumentMut, original: &str) -> String {
+    let mut rendered = doc.to_string();
+
+    // `toml_edit` keeps the original line endings but uses `\n` for everything it adds
+    let newline = if original.contains("\r\n") {
+        rendered = rendered.replace("\r\n", "\n").replace('\n', "\r\n");
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    if original.is_empty() || original.ends_with('\n') {
+        if !rendered.is_empty() && !rendered.ends_with('\n') {
+            rendered.push_str(newline);
+        }
+    } else if let Some(trimmed) = rendered.strip_suffix(newline) {
+        rendered.truncate(trimmed.len());
+    }
+
+    rendered
+}
+
+fn update_dependencies_table(
+    deps_table: &mut dyn toml_edit::TableLike,
+    table_name: &str,
+    kind: DependencyKind,
+    manifest_path: &Utf8Path,
+    common_deps: &HashMap<String, CommonDependency>,
+    config: &Config,
+) -> AppResult<bool> {
+    let mut modified = false;
+
+    for (common_name, info) in common_deps {
+        if config.excludes(common_name, kind) {
+            continue;
+        }
+        // Members spelling a common dependency differently keep their spelling, the workspace
+        // has an entry under each of them
+        for name in std::iter::once(common_name).chain(&info.spellings) {
+            if let Some(item) = deps_table.get_mut(name) {
+                // Entries already inheriting from the workspace are left as written, except for
+                // keeping the default features the workspace entry no longer enables
+                if is_inherited(item) {
+                    if info.inherited_defaults
+                        && let Some(table) = item.as_table_like_mut()
+                        && keep_default_features(table, info.mixed_default_features)
+                    {
+                        if let Some(table) = item.as_inline_table_mut() {
+                            table.fmt();
+                        }
+                        modified = true;
+                    }
+                    continue;
+                }
+                // The occurrences from an excluded source weren't counted, e.g. a git fork of a
+                // crate the other members use from crates.io
+                if excludes_source(config, item) {
+                    continue;
+                }
+                // Nor were the optional ones with `exclude_optional`
+                if config.exclude_optional
+                    && item.get("optional").and_then(|o| o.as_bool()) == Some(true)
+                {
+                    continue;
+                }
+                match item {
+                    toml_edit::Item::Value(toml_edit::Value::String(_)) => {
+                        // Replace with workspace = true
+                        let mut dep_table = toml_edit::Table::new();
+                        dep_table.set_implicit(true);
+                        dep_table["workspace"] = toml_edit::value(true);
+                        keep_default_features(&mut dep_table, info.mixed_default_features);
+                        *item = dep_table.into_inline_table().into();
+                        modified = true;
+                    }
+                    toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
+                        // Keep existing configuration but add workspace = true
+                        // Remove the version and registry fields if they exist, the workspace
+                        // entry carries them, along with the path of local dependencies
+                        check_version(table, name, table_name, manifest_path)?;
+                        table.remove("version");
+                        table.remove("registry");
+                        if info.path.is_some() {
+                            table.remove("path");
+                        }
+                        // Add workspace = true
+                        let entry = table.entry("workspace").or_insert_with(|| {
+                            modified = true;
+                            toml_edit::Value::Boolean(toml_edit::Formatted::new(true))
+                        });
+
+                        match entry.as_bool() {
+                            Some(true) => {}
+                            Some(false) => {
+                                *entry = toml_edit::Value::Boolean(toml_edit::Formatted::new(true));
+                                modified = true;
+                            }
+                            None => {
+                                return Err(AppError::DependencyUpdate(
+                                    name.clone(),
+                                    table_name.to_string(),
+                                    format!(
+                                        "'workspace' is {}, expected a boolean",
+                                        entry.type_name()
+                                    ),
+                                    manifest_path.to_path_buf(),
+                                ));
+                            }
+                        }
+
+                        modified |= strip_workspace_features(table, &info.features);
+                        modified |= keep_default_features(table, info.mixed_default_features);
+
+                        // Conventionally `workspace = true` comes first, and removing the version
+                        // leaves the spacing of the remaining keys uneven
+                        table.sort_values_by(|key, _, other, _| {
+                            (other.get() == "workspace").cmp(&(key.get() == "workspace"))
+                        });
+                        table.fmt();
+                    }
+                    toml_edit::Item::Table(table) => {
+                        // Keep existing configuration but add workspace = true
+                        // Remove the version and registry fields if they exist, the workspace
+                        // entry carries them, along with the path of local dependencies
+                        check_version(table, name, table_name, manifest_path)?;
+                        table.remove("version");
+                        table.remove("registry");
+                        if info.path.is_some() {
+                            table.remove("path");
+                        }
+                        // Add workspace = true
+                        let entry = table.entry("workspace").or_insert_with(|| {
+                            modified = true;
+                            toml_edit::value(true)
+                        });
+
+                        match entry.as_bool() {
+                            Some(true) => {}
+                            Some(false) => {
+                                *entry = toml_edit::value(true);
+                                modified = true;
+                            }
+                            None => {
+                                return Err(AppError::DependencyUpdate(
+                                    name.clone(),
+                                    table_name.to_string(),
+                                    format!(
+                                        "'workspace' is {}, expected a boolean",
+                                        entry.type_name()
+                                    ),
+                                    manifest_path.to_path_buf(),
+                                ));
+                            }
+                        }
+
+                        modified |= strip_workspace_features(table, &info.features);
+                        modified |= keep_default_features(table, info.mixed_default_features);
+                    }
+
+                    toml_edit::Item::ArrayOfTables(tables) => {
+                        for table in tables.iter_mut() {
+                            modified |= update_dependencies_table(
+                                table,
+                                table_name,
+                                kind,
+                                manifest_path,
+                                common_deps,
+                                config,
+                            )?;
+                        }
+                    }
+                    _ => {
+                        warn!(
+                            "{} skipping '{}' in [{}] of {}: unexpected {} value",
+                            "Warning:".yellow().bold(),
+                            name,
+                            table_name,
+                            manifest_path,
+                            item.type_name()
+                        );
+                    }
+                }
+                modified |= restyle_entry(deps_table, name, config.table_style);
+            }
+        }
+    }
+
+    Ok(modified)
+}
+
+/// Keeps the default features of a member entry inheriting a workspace entry that disables them,
+/// returning whether it changed. Members stating `default-features` keep their choice, the others
+/// get `default-features = true`, or the `default` feature when members disagree on them so the
+/// entry only adds to what the workspace enables
+fn keep_default_features(
+    table: &mut dyn toml_edit::TableLike,
+    mixed_default_features: bool,
+) -> bool {
+    if table.contains_key("default-features") {
+        return false;
+    }
+    if !mixed_default_features {
+        table.insert("default-features", toml_edit::value(true));
+        return true;
+    }
+    match table
+        .get_mut("features")
+        .and_then(|item| item.as_array_mut())
+    {
+        Some(features) if features.iter().any(|f| f.as_str() == Some("default")) => false,
+        Some(features) => {
+            features.push("default");
+            true
+        }
+        None => {
+            table.insert(
+                "features",
+                toml_edit::value(toml_edit::Array::from_iter(["default"])),
+            );
+            true
+        }
+    }
+}
+
+/// Rewrites a dependency entry in the requested form, returning whether it changed. Entries of
+/// an inline dependency table can't become sections and stay inline
+fn restyle_entry(deps_table: &mut dyn toml_edit::TableLike, name: &str, style: TableStyle) -> bool {
+    let Some(item) = deps_table.get_mut(name) else {
+        return false;
+    };
+    let restyled = match (style, &*item) {
+        (TableStyle::Inline, toml_edit::Item::Table(table)) => {
+            let mut table = table.clone().into_inline_table();
+            table.fmt();
+            *item = toml_edit::value(table);
+            true
+        }
+        (TableStyle::Section, toml_edit::Item::Value(toml_edit::Value::InlineTable(table))) => {
+            let mut table = table.clone().into_table();
+            table.fmt();
+            deps_table.insert(name, toml_edit::Item::Table(table));
+            deps_table.get(name).is_some_and(toml_edit::Item::is_table)
+        }
+        _ => false,
+    };
+    // The spacing around a key differs between a section header and a key-value pair
+    if restyled && let Some(mut key) = deps_table.key_mut(name) {
+        key.leaf_decor_mut().clear();
+    }
+    restyled
+}
+
+/// Renames the root `workspace.dependencies` entries spelled differently from the crate members
+/// use (e.g. `Serde` for `serde`), which members inheriting the crate wouldn't find, or fails
+/// without `fix_root_spelling`. Renamed crates (with a `package` key) are left alone
+fn fix_root_spellings(
+    doc: &mut DocumentMut,
+    common_deps: &HashMap<String, CommonDependency>,
+    config: &Config,
+) -> AppResult<bool> {
+    let Some(deps_table) = doc["workspace"]["dependencies"].as_table_like_mut() else {
+        return Ok(false);
+    };
+    let mut mismatches: Vec<(String, String)> = deps_table
+        .iter()
+        .filter(|(_, item)| item.get("package").is_none())
+        .filter_map(|(key, _)| {
+            let name = common_deps
+                .keys()
+                .find(|name| *name != key && normalized_name(name) == normalized_name(key))?;
+            Some((key.to_string(), name.clone()))
+        })
+        .filter(|(_, name)| !deps_table.contains_key(name))
+        .collect();
+    if mismatches.is_empty() {
+        return Ok(false);
+    }
+    mismatches.sort();
+    if !config.fix_root_spelling {
+        return Err(AppError::RootSpelling(mismatches));
+    }
+    for (key, name) in mismatches {
+        rename_key(deps_table, &key, &name);
+        info!(
+            "  - {} '{}' to '{}'",
+            "Renamed root entry".yellow(),
+            key,
+            name
+        );
+    }
+    Ok(true)
+}
+
+/// Renames the `from` key of a table to `to`, keeping its position and comments
+fn rename_key(table: &mut dyn toml_edit::TableLike, from: &str, to: &str) {
+    let names: Vec<String> = table.iter().map(|(name, _)| name.to_string()).collect();
+    let mut entries = Vec::new();
+    for name in names {
+        let Some(key) = table.get_key_value(&name).map(|(key, _)| key.clone()) else {
+            continue;
+        };
+        let Some(item) = table.remove(&name) else {
+            continue;
+        };
+        let key = if name == from {
+            toml_edit::Key::new(to)
+                .with_leaf_decor(key.leaf_decor().clone())
+                .with_dotted_decor(key.dotted_decor().clone())
+        } else {
+            key
+        };
+        entries.push((key, item));
+    }
+    for (key, item) in entries {
+        table.entry_format(&key).or_insert(item);
+    }
+}
+
+/// Fails on a `version` that isn't a string (e.g. `version = 1`), which cargo would reject,
+/// rather than silently dropping it in favor of the workspace entry
+fn check_version(
+    table: &dyn toml_edit::TableLike,
+    name: &str,
+    table_name: &str,
+    manifest_path: &Utf8Path,
+) -> AppResult<()> {
+    match table.get("version") {
+        Some(version) if !version.is_str() => Err(AppError::DependencyUpdate(
+            name.to_string(),
+            table_name.to_string(),
+            format!("'version' is {}, expected a string", version.type_name()),
+            manifest_path.to_path_buf(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Removes the features already enabled by the workspace entry from a member dependency,
+/// dropping the `features` key once it's empty
+fn strip_workspace_features(table: &mut dyn toml_edit::TableLike, features: &[String]) -> bool {
+    let Some(member_features) = table
+        .get_mut("features")
+        .and_then(|item| item.as_array_mut())
+    else {
+        return false;
+    };
+
+    let len = member_features.len();
+    member_features.retain(|feature| {
+        !feature
+            .as_str()
+            .is_some_and(|feature| features.iter().any(|f| f == feature))
+    });
+    let modified = member_features.len() != len;
+    if modified {
+        member_features.fmt();
+    }
+    if member_features.is_empty() {
+        table.remove("features");
+    }
+
+    modified
+}
@@ -0,0 +1,199 @@
+use crate::{
+    AppError, AppResult, CommonDependency, error::toml_err, read_manifest, trace,
+    workspace_dependency_version,
+};
+use cargo_metadata::{
+    Metadata,
+    semver::{Version, VersionReq},
+};
+use colored::Colorize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+};
+use tracing::{debug, warn};
+
+/// A member whose locked version of a dependency doesn't satisfy the requirement it would inherit
+#[derive(Debug)]
+pub struct LockChange {
+    /// Name of the dependency
+    pub name: String,
+    /// Name of the member
+    pub member: String,
+    /// Version the member currently resolves to in `Cargo.lock`
+    pub locked: Version,
+    /// Requirement of the workspace entry the member would inherit
+    pub requirement: VersionReq,
+}
+
+/// Compares the requirement each common dependency is hoisted with, or the one the root already
+/// declares, against the versions members resolve to in `Cargo.lock`. Nothing is reported without
+/// a lockfile
+pub fn lock_changes(
+    metadata: &Metadata,
+    common_deps: &HashMap<String, CommonDependency>,
+) -> AppResult<Vec<LockChange>> {
+    let Some(lock) = read_lock(metadata)? else {
+        debug!("No Cargo.lock to compare the hoisted versions with");
+        return Ok(Vec::new());
+    };
+
+    let (_, root) = read_manifest(metadata.workspace_root.join("Cargo.toml"))?;
+    let workspace_deps = root
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"));
+    let requirement = |name: &str| {
+        workspace_deps
+            .and_then(|deps| deps.get(name))
+            .and_then(workspace_dependency_version)
+            .and_then(|version| VersionReq::parse(version).ok())
+            .unwrap_or_else(|| common_deps[name].dep.req.clone())
+    };
+
+    let mut changes = Vec::new();
+    for (member, name, locked) in member_locked_versions(metadata, &lock, common_deps)? {
+        let requirement = requirement(name);
+        if !requirement.matches(&locked) {
+            changes.push(LockChange {
+                name: name.to_string(),
+                member,
+                locked,
+                requirement,
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.name.cmp(&b.name).then(a.member.cmp(&b.member)));
+    Ok(changes)
+}
+
+/// Pins each common dependency to the exact version its members resolve to in `Cargo.lock`, for
+/// `--version-source lockfile`. Members locking different versions get the highest one every
+/// member requirement accepts
+pub(crate) fn pin_locked_versions(
+    metadata: &Metadata,
+    common_deps: &mut HashMap<String, CommonDependency>,
+) -> AppResult<()> {
+    let lock_path = metadata.workspace_root.join("Cargo.lock");
+    let lock = read_lock(metadata)?.ok_or(AppError::LockfileMissing(lock_path))?;
+
+    let mut locked: HashMap<&str, BTreeSet<Version>> = HashMap::new();
+    for (_, name, version) in member_locked_versions(metadata, &lock, common_deps)? {
+        locked.entry(name).or_default().insert(version);
+    }
+    let mut pinned = HashMap::new();
+    for (name, info) in common_deps.iter() {
+        let Some(versions) = locked.get(name.as_str()) else {
+            warn!(
+                "{} '{}' is not in Cargo.lock, hoisting it as {}",
+                "Warning:".yellow().bold(),
+                name,
+                info.dep.req
+            );
+            continue;
+        };
+        let version = versions
+            .iter()
+            .rev()
+            .find(|version| {
+                info.requirements
+                    .iter()
+                    .all(|(_, req)| req.matches(version))
+            })
+            .ok_or_else(|| {
+                AppError::LockedVersionConflict(
+                    name.clone(),
+                    versions
+                        .iter()
+                        .map(Version::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )
+            })?;
+        let req = VersionReq::parse(&format!("={}", version)).map_err(|e| {
+            AppError::Metadata(format!("invalid locked version '{}': {}", version, e))
+        })?;
+        debug!("Pinning '{}' to {} from Cargo.lock", name, req);
+        pinned.insert(name.clone(), req);
+    }
+    for (name, req) in pinned {
+        if let Some(info) = common_deps.get_mut(&name) {
+            info.dep.req = req;
+        }
+    }
+    Ok(())
+}
+
+/// The parsed `Cargo.lock` of the workspace, `None` when there is none
+fn read_lock(metadata: &Metadata) -> AppResult<Option<toml_edit::DocumentMut>> {
+    let lock_path = metadata.workspace_root.join("Cargo.lock");
+    let Ok(content) = fs::read_to_string(&lock_path) else {
+        return Ok(None);
+    };
+    trace::read(lock_path.as_std_path())?;
+    content
+        .parse::<toml_edit::DocumentMut>()
+        .map(Some)
+        .map_err(|e| toml_err(e, &lock_path, &content))
+}
+
+/// The version each member resolves each of the common dependencies it uses to, as member name,
+/// dependency name and locked version
+fn member_locked_versions<'a>(
+    metadata: &Metadata,
+    lock: &toml_edit::DocumentMut,
+    common_deps: &'a HashMap<String, CommonDependency>,
+) -> AppResult<Vec<(String, &'a str, Version)>> {
+    let lock_path = metadata.workspace_root.join("Cargo.lock");
+    let packages = lock
+        .get("package")
+        .and_then(|packages| packages.as_array_of_tables())
+        .ok_or_else(|| AppError::Metadata(format!("no [[package]] entries in '{}'", lock_path)))?;
+
+    // Versions of each locked package, a dependency only names its version when there are several
+    let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+    for package in packages {
+        if let (Some(name), Some(version)) = (
+            package.get("name").and_then(|name| name.as_str()),
+            package.get("version").and_then(|version| version.as_str()),
+        ) {
+            versions.entry(name).or_default().push(version);
+        }
+    }
+
+    let mut locked_versions = Vec::new();
+    for member in metadata.workspace_packages() {
+        let version = member.version.to_string();
+        let Some(locked) = packages.iter().find(|package| {
+            package.get("name").and_then(|name| name.as_str()) == Some(member.name.as_str())
+                && package.get("version").and_then(|v| v.as_str()) == Some(version.as_str())
+        }) else {
+            continue;
+        };
+        let dependencies = locked
+            .get("dependencies")
+            .and_then(|deps| deps.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|dep| dep.as_str());
+        for dep in dependencies {
+            let mut parts = dep.split_whitespace();
+            let Some((name, _)) = parts
+                .next()
+                .and_then(|name| common_deps.get_key_value(name))
+            else {
+                continue;
+            };
+            let locked_version =
+                match (parts.next(), versions.get(name.as_str()).map(Vec::as_slice)) {
+                    (Some(version), _) => version,
+                    (None, Some([version])) => version,
+                    _ => continue,
+                };
+            let Ok(locked_version) = Version::parse(locked_version) else {
+                continue;
+            };
+            locked_versions.push((member.name.to_string(), name.as_str(), locked_version));
+        }
+    }
+    Ok(locked_versions)
+}
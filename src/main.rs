@@ -1,15 +1,29 @@
-#![doc = include_str!("../README.md")]
-
-use cargo_metadata::{Dependency, Metadata, MetadataCommand, camino::Utf8PathBuf};
-use clap::{ArgAction, Args, Parser};
+use cargo_easy_dep::{
+    Config, ConflictPolicy, Explanation, FeaturesStrategy, OutputFormat, RunStats, TableStyle,
+    VersionSource, run,
+};
+use cargo_metadata::DependencyKind;
+use clap::{
+    ArgAction, ArgMatches, Args, ColorChoice, CommandFactory, FromArgMatches, Parser, Subcommand,
+    builder::{PossibleValuesParser, TypedValueParser},
+    error::ErrorKind,
+    parser::ValueSource,
+};
+use clap_complete::Shell;
 use colored::Colorize;
 use std::{
-    collections::HashMap,
-    error::Error,
-    fmt, fs,
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, IsTerminal, Write},
     path::{Path, PathBuf},
+    process::ExitCode,
 };
-use toml_edit::{self, DocumentMut};
+use tracing::error;
+use tracing_subscriber::EnvFilter;
+
+/// Options that only make sense on the command line, not in the `--config` file
+const COMMAND_LINE_ONLY: [&str; 2] = ["config", "print_config_schema"];
 
 // See also `clap_cargo::style::CLAP_STYLING`
 const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling::Styles::styled()
@@ -32,8 +46,12 @@ enum CargoCli {
 #[derive(Args)]
 #[command(about, version)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Minimum number of occurrences to consider a dependency common
     #[clap(
+        global = true,
         short,
         long,
         default_value = "2",
@@ -42,457 +60,1071 @@ struct Cli {
     )]
     min_occurrences: u32,
 
-    /// Path to workspace root (defaults to current directory)
-    #[clap(short, long, env = "CARGO_EASY_DEP_WORKSPACE_ROOT")]
-    workspace_root: Option<PathBuf>,
+    /// Minimum percentage of workspace members using a dependency to consider it common (the
+    /// stricter of this and --min-occurrences applies)
+    #[clap(
+        global = true,
+        long,
+        env = "CARGO_EASY_DEP_THRESHOLD_PERCENT",
+        value_parser = clap::value_parser!(u8).range(1..=100)
+    )]
+    threshold_percent: Option<u8>,
+
+    /// Hoist every dependency except path ones, regardless of how many members use it (e.g. for
+    /// strict version pinning policies)
+    #[clap(
+        global = true,
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["min_occurrences", "threshold_percent", "deps"]
+    )]
+    all: bool,
+
+    /// Path to workspace root (defaults to current directory), can be repeated to process
+    /// several independent workspaces
+    #[clap(global = true, short, long, env = "CARGO_EASY_DEP_WORKSPACE_ROOT")]
+    workspace_root: Vec<PathBuf>,
 
-    /// Suppress all output
+    /// Path to the Cargo.toml of the workspace, as an alternative to --workspace-root
     #[clap(
+        global = true,
+        long,
+        value_name = "PATH",
+        conflicts_with = "workspace_root"
+    )]
+    manifest_path: Option<PathBuf>,
+
+    /// Read default options from this TOML file, whose keys are the long option names (e.g.
+    /// `min-occurrences = 3`). Options given on the command line or through their environment
+    /// variable take precedence
+    #[clap(global = true, long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Print the JSON Schema of the --config file, e.g. for editors to validate it, and exit
+    #[clap(long, action = ArgAction::SetTrue)]
+    print_config_schema: bool,
+
+    /// Only process the workspace rooted exactly at each --workspace-root, failing instead of
+    /// falling back to an enclosing parent workspace
+    #[clap(global = true, long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_NO_RECURSE")]
+    no_recurse: bool,
+
+    /// Only consider the members with files changed since this git ref (e.g. `origin/main`),
+    /// uncommitted changes included
+    #[clap(global = true, long, value_name = "REF", env = "CARGO_EASY_DEP_SINCE")]
+    since: Option<String>,
+
+    /// Only consider the members whose package names this file lists, one per line (blank lines
+    /// and `#` comments are ignored), e.g. as computed by another tool
+    #[clap(
+        global = true,
+        long,
+        value_name = "FILE",
+        env = "CARGO_EASY_DEP_MEMBERS_FROM_FILE"
+    )]
+    members_from_file: Option<PathBuf>,
+
+    /// Suppress progress and results, errors are still printed
+    #[clap(
+        global = true,
         short,
         long,
         action = ArgAction::SetTrue,
         env = "CARGO_EASY_DEP_QUIET"
     )]
     quiet: bool,
-}
 
-#[derive(Debug)]
-enum AppError {
-    Metadata(String),
-    Io(std::io::Error, PathBuf),
-    TomlParse(toml_edit::TomlError, PathBuf),
-    WorkspaceUpdate(String),
-    MemberUpdate(String, Utf8PathBuf),
-}
+    /// Suppress all output, errors included, leaving only the exit status
+    #[clap(
+        global = true,
+        long,
+        action = ArgAction::SetTrue,
+        env = "CARGO_EASY_DEP_SILENT"
+    )]
+    silent: bool,
 
-impl fmt::Display for AppError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            AppError::Metadata(msg) => write!(f, "Failed to retrieve cargo metadata: {}", msg),
-            AppError::Io(err, path) => write!(f, "IO error at '{}': {}", path.display(), err),
-            AppError::TomlParse(err, path) => {
-                write!(f, "TOML parse error in '{}': {}", path.display(), err)
-            }
-            AppError::WorkspaceUpdate(msg) => {
-                write!(f, "Failed to update workspace Cargo.toml: {}", msg)
-            }
-            AppError::MemberUpdate(msg, path) => write!(
-                f,
-                "Failed to update member Cargo.toml at '{}': {}",
-                path, msg
-            ),
-        }
-    }
-}
+    /// Coloring of the output: `auto` only colors terminals and honors `NO_COLOR`, `CLICOLOR`
+    /// and `CLICOLOR_FORCE`
+    #[clap(
+        global = true,
+        long,
+        value_name = "WHEN",
+        value_enum,
+        default_value_t = ColorChoice::Auto
+    )]
+    color: ColorChoice,
 
-impl Error for AppError {}
+    /// Increase diagnostic verbosity (-v for debug, -vv for trace), `RUST_LOG` takes precedence
+    #[clap(
+        global = true,
+        short,
+        long,
+        action = ArgAction::Count,
+        conflicts_with_all = ["quiet", "silent"]
+    )]
+    verbose: u8,
 
-impl From<cargo_metadata::Error> for AppError {
-    fn from(err: cargo_metadata::Error) -> Self {
-        AppError::Metadata(err.to_string())
-    }
-}
+    /// Write the updated root Cargo.toml to this path instead of in place (`-` for stdout).
+    /// Member Cargo.toml files are left untouched in this mode
+    #[clap(short, long, env = "CARGO_EASY_DEP_OUTPUT")]
+    output: Option<PathBuf>,
 
-fn io_err(err: std::io::Error, path: impl Into<PathBuf>) -> AppError {
-    AppError::Io(err, path.into())
-}
+    /// Print why each dependency was or wasn't hoisted
+    #[clap(global = true, long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_EXPLAIN")]
+    explain: bool,
+
+    /// Only hoist this dependency, regardless of the occurrence threshold (can be repeated)
+    #[clap(
+        global = true,
+        long = "dep",
+        value_name = "NAME",
+        value_delimiter = ','
+    )]
+    deps: Vec<String>,
+
+    /// What to do when members require different versions of a dependency: abort (`error`),
+    /// skip it with a warning (`warn`) or hoist the first version seen (`first`)
+    #[clap(
+        global = true,
+        long,
+        value_name = "POLICY",
+        default_value = "first",
+        value_parser = PossibleValuesParser::new(["error", "warn", "first"]).map(parse_conflict_policy),
+        env = "CARGO_EASY_DEP_ON_CONFLICT"
+    )]
+    on_conflict: ConflictPolicy,
+
+    /// Where the versions of the new root workspace.dependencies entries come from: the
+    /// requirement members declare (`manifest`) or the exact version they resolve to in
+    /// Cargo.lock (`lockfile`, e.g. `=1.0.200`)
+    #[clap(
+        long,
+        value_name = "SOURCE",
+        default_value = "manifest",
+        value_parser = PossibleValuesParser::new(["manifest", "lockfile"]).map(parse_version_source),
+        env = "CARGO_EASY_DEP_VERSION_SOURCE"
+    )]
+    version_source: VersionSource,
+
+    /// Dependencies to leave untouched so members keep their explicit `version` (e.g. for
+    /// publishing requirements)
+    #[clap(
+        global = true,
+        long,
+        value_name = "NAME",
+        value_delimiter = ',',
+        env = "CARGO_EASY_DEP_KEEP_VERSION"
+    )]
+    keep_version: Vec<String>,
+
+    /// Only hoist normal dependencies (combinable with the other `--only-*` flags)
+    #[clap(global = true, long, action = ArgAction::SetTrue)]
+    only_normal: bool,
+
+    /// Only hoist dev-dependencies (combinable with the other `--only-*` flags)
+    #[clap(global = true, long, action = ArgAction::SetTrue)]
+    only_dev: bool,
+
+    /// Only hoist build-dependencies (combinable with the other `--only-*` flags)
+    #[clap(global = true, long, action = ArgAction::SetTrue)]
+    only_build: bool,
+
+    /// Dependencies to leave unhoisted in `[dependencies]` only
+    #[clap(global = true, long, value_name = "NAME", value_delimiter = ',')]
+    exclude_normal: Vec<String>,
+
+    /// Dependencies to leave unhoisted in `[dev-dependencies]` only
+    #[clap(global = true, long, value_name = "NAME", value_delimiter = ',')]
+    exclude_dev: Vec<String>,
+
+    /// Dependencies to leave unhoisted in `[build-dependencies]` only (e.g. `cc`)
+    #[clap(global = true, long, value_name = "NAME", value_delimiter = ',')]
+    exclude_build: Vec<String>,
+
+    /// Leave git dependencies unhoisted, in every dependency kind
+    #[clap(
+        global = true,
+        long,
+        action = ArgAction::SetTrue,
+        env = "CARGO_EASY_DEP_EXCLUDE_GIT"
+    )]
+    exclude_git: bool,
+
+    /// Leave optional dependencies unhoisted, as they're tied to the features of each member
+    #[clap(
+        global = true,
+        long,
+        action = ArgAction::SetTrue,
+        env = "CARGO_EASY_DEP_EXCLUDE_OPTIONAL"
+    )]
+    exclude_optional: bool,
+
+    /// Registries whose dependencies are left unhoisted, by the name used in `registry = "..."`
+    /// (`crates-io` for crates.io)
+    #[clap(
+        global = true,
+        long,
+        value_name = "NAME",
+        value_delimiter = ',',
+        env = "CARGO_EASY_DEP_EXCLUDE_REGISTRY"
+    )]
+    exclude_registry: Vec<String>,
+
+    /// Treat members under these directories as peripheral: their dependencies are converted but
+    /// not counted (`examples` and `benches` when no directory is given)
+    #[clap(
+        global = true,
+        long,
+        value_name = "DIR",
+        num_args = 0..,
+        value_delimiter = ',',
+        default_missing_values = ["examples", "benches"]
+    )]
+    dev_only_members: Vec<PathBuf>,
+
+    /// Only print the dependencies ranked by how many members duplicate them and how many
+    /// distinct version requirements they have, without modifying anything
+    #[clap(long, action = ArgAction::SetTrue)]
+    report: bool,
+
+    /// Only print how many characters the member version declarations of the common
+    /// dependencies take, most costly first, without modifying anything
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "report")]
+    dedupe_only: bool,
+
+    /// Only print the root workspace.dependencies block hoisting would write, as TOML to paste
+    /// into the root Cargo.toml, without modifying anything
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["report", "dedupe_only"]
+    )]
+    print_workspace_deps: bool,
+
+    /// Only print the root workspace.dependencies entries no member inherits, without modifying
+    /// anything. Combined with --locked, fails when there are any
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["report", "dedupe_only", "print_workspace_deps"]
+    )]
+    report_unused_workspace_deps: bool,
+
+    /// Format of the `list`, --explain, --report, --dedupe-only and --report-unused-workspace-deps
+    /// output. `github-annotations` prints a GitHub Actions notice for each Cargo.toml file
+    /// updated (or that would be with --dry-run or --locked), and reports as text
+    #[clap(
+        global = true,
+        long,
+        default_value = "text",
+        value_parser = PossibleValuesParser::new(["text", "json", "github-annotations"]).map(parse_format),
+        env = "CARGO_EASY_DEP_FORMAT"
+    )]
+    format: OutputFormat,
+
+    /// Print a final `easy-dep: root=<modified|unchanged> members=<updated>/<total> deps=<count>`
+    /// line for scripts
+    #[clap(long, action = ArgAction::SetTrue)]
+    summary_line: bool,
+
+    /// Leave out the final success message while still printing progress
+    #[clap(long, action = ArgAction::SetTrue)]
+    no_banner: bool,
+
+    /// Leave out the hint to run `cargo check` after manifests were rewritten
+    #[clap(long, action = ArgAction::SetTrue)]
+    no_hints: bool,
+
+    /// Print how long each phase took and how much was parsed and written to stderr
+    #[clap(long, action = ArgAction::SetTrue)]
+    stats: bool,
+
+    /// Require the manifests to be fully hoisted already and fail if any file would be updated
+    #[clap(long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_LOCKED")]
+    locked: bool,
+
+    /// Warn about members declaring the same crate more than once for the same dependency kind
+    #[clap(
+        global = true,
+        long,
+        action = ArgAction::SetTrue,
+        env = "CARGO_EASY_DEP_WARN_DUPLICATES"
+    )]
+    warn_duplicates: bool,
+
+    /// Only add dependencies to the root workspace.dependencies, leaving members untouched
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "no_root_update")]
+    no_member_update: bool,
+
+    /// Only convert members to dependencies already declared in the root workspace.dependencies,
+    /// leaving the root untouched
+    #[clap(long, action = ArgAction::SetTrue)]
+    no_root_update: bool,
+
+    /// Remove root workspace.dependencies entries no member inherits anymore
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "no_root_update")]
+    prune: bool,
+
+    /// Rename the root workspace.dependencies entries spelled differently from the crate members
+    /// use (e.g. `Serde` for `serde`) instead of failing
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "no_root_update")]
+    fix_root_spelling: bool,
+
+    /// Remove the [dependencies], [dev-dependencies] and [build-dependencies] tables left empty in
+    /// the members, target-specific ones included
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "no_member_update")]
+    prune_empty_tables: bool,
+
+    /// Skip the members whose Cargo.toml is a symlink (e.g. in vendored setups) with a warning,
+    /// instead of updating the file it points to
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with = "no_member_update")]
+    no_follow_symlinks: bool,
+
+    /// Which features members enable move to the workspace entry, removed from the members:
+    /// none of them (`none`), those every member enables (`intersection`), every one any member
+    /// enables (`union`), or the list only when every member enables exactly the same one
+    /// (`uniform`)
+    #[clap(
+        long,
+        value_name = "STRATEGY",
+        default_value = "none",
+        value_parser = PossibleValuesParser::new(["none", "intersection", "union", "uniform"])
+            .map(parse_features_strategy),
+        env = "CARGO_EASY_DEP_FEATURES_STRATEGY"
+    )]
+    features_strategy: FeaturesStrategy,
+
+    /// Same as --features-strategy intersection
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with = "features_strategy",
+        env = "CARGO_EASY_DEP_HOIST_FEATURES"
+    )]
+    hoist_features: bool,
+
+    /// Same as --features-strategy uniform
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with_all = ["features_strategy", "hoist_features"],
+        env = "CARGO_EASY_DEP_DEDUPE_FEATURES"
+    )]
+    dedupe_features: bool,
+
+    /// Also hoist dependencies declared with both a `path` and a `version` (e.g. published
+    /// sibling crates), writing both to the workspace entry
+    #[clap(
+        global = true,
+        long,
+        action = ArgAction::SetTrue,
+        env = "CARGO_EASY_DEP_HOIST_PATH_VERSIONS"
+    )]
+    hoist_path_versions: bool,
+
+    /// Skip dependencies whose version requirement is clearly pre-1.0 (`0.x`)
+    #[clap(global = true, long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_SKIP_PRE_1")]
+    skip_pre_1: bool,
 
-fn toml_err(err: toml_edit::TomlError, path: impl Into<PathBuf>) -> AppError {
-    AppError::TomlParse(err, path.into())
+    /// Hoist dependencies whose version requirement is a wildcard (`*`), which are skipped with a
+    /// warning by default since a wildcard workspace entry breaks `cargo publish`
+    #[clap(
+        global = true,
+        long,
+        action = ArgAction::SetTrue,
+        env = "CARGO_EASY_DEP_ALLOW_WILDCARD"
+    )]
+    allow_wildcard: bool,
+
+    /// Write version requirements to the workspace in their shortest equivalent form (e.g. `1`
+    /// for `^1.0.0`) instead of the caret form
+    #[clap(long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_NORMALIZE_VERSIONS")]
+    normalize_versions: bool,
+
+    /// Group the root workspace.dependencies entries by the first kind they're used as, under
+    /// `# normal`, `# dev` and `# build` comment headers
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with = "no_root_update",
+        env = "CARGO_EASY_DEP_GROUP_BY_KIND"
+    )]
+    group_by_kind: bool,
+
+    /// Keep the root workspace.dependencies entries sorted by name (within their group with
+    /// --group-by-kind). Combined with --locked, fails when they aren't
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with = "no_root_update",
+        env = "CARGO_EASY_DEP_KEEP_SORTED"
+    )]
+    keep_sorted: bool,
+
+    /// Move the `[patch.<source>]` entries as many members as a dependency needs to be hoisted
+    /// declare identically to the root Cargo.toml, where cargo applies them, removing them from
+    /// the members (they are only reported otherwise)
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with = "no_root_update",
+        env = "CARGO_EASY_DEP_APPLY_TO_PATCH"
+    )]
+    apply_to_patch: bool,
+
+    /// Write the rewritten member entries and the new root entries as inline tables, e.g.
+    /// `serde = { workspace = true }`, instead of keeping the form of each entry
+    #[clap(long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_INLINE_TABLES")]
+    inline_tables: bool,
+
+    /// Write the rewritten member entries and the new root entries as sections, e.g.
+    /// `[dependencies.serde]`, instead of keeping the form of each entry
+    #[clap(
+        long,
+        action = ArgAction::SetTrue,
+        conflicts_with = "inline_tables",
+        env = "CARGO_EASY_DEP_SECTION_TABLES"
+    )]
+    section_tables: bool,
+
+    /// Write the new root workspace.dependencies entries as inline tables (`inline`) or as
+    /// `[workspace.dependencies.<name>]` sections (`expanded`), whatever form member entries take
+    #[clap(
+        long,
+        value_name = "STYLE",
+        value_parser = PossibleValuesParser::new(["inline", "expanded"]).map(parse_root_table_style),
+        env = "CARGO_EASY_DEP_ROOT_TABLE_STYLE"
+    )]
+    root_table_style: Option<TableStyle>,
+
+    /// Only hoist dependencies used as every listed kind across the workspace (e.g.
+    /// `normal,dev`)
+    #[clap(
+        global = true,
+        long,
+        value_name = "KIND",
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(["normal", "dev", "build"]).map(parse_kind),
+        env = "CARGO_EASY_DEP_REQUIRE_KINDS"
+    )]
+    require_kinds: Vec<DependencyKind>,
+
+    /// Warn when a member's version in Cargo.lock doesn't satisfy the requirement it would
+    /// inherit, i.e. when hoisting would change what it resolves to
+    #[clap(long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_CHECK_LOCK")]
+    check_lock: bool,
+
+    /// Write a unified diff of every change to this file, suitable for `git apply`, instead of
+    /// updating the manifests
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with = "output",
+        env = "CARGO_EASY_DEP_EMIT_PATCH"
+    )]
+    emit_patch: Option<PathBuf>,
+
+    /// Append a timestamped line for every file read and written, and whether it was modified,
+    /// to this file for auditing
+    #[clap(
+        global = true,
+        long,
+        value_name = "FILE",
+        env = "CARGO_EASY_DEP_TRACE_FILE"
+    )]
+    trace_file: Option<PathBuf>,
+
+    /// Process a standalone package even though it isn't part of a workspace, or a workspace
+    /// with more members than --max-members
+    #[clap(long, action = ArgAction::SetTrue)]
+    force: bool,
+
+    /// Refuse to update a workspace with more members than this, as a guard against pointing
+    /// at the wrong directory (overridden by --force)
+    #[clap(long, value_name = "N", env = "CARGO_EASY_DEP_MAX_MEMBERS")]
+    max_members: Option<usize>,
+
+    /// Fail when a dependency members require in several ways has more distinct requirements per
+    /// member using it than this ratio (e.g. 0.5), before anything is written. With --report, the
+    /// offenders are listed after the report
+    #[clap(
+        global = true,
+        long,
+        value_name = "RATIO",
+        value_parser = parse_ratio,
+        env = "CARGO_EASY_DEP_MAX_FRAGMENTATION"
+    )]
+    max_fragmentation: Option<f64>,
+
+    /// Add a `[workspace]` table to the root Cargo.toml when it has none, instead of failing
+    #[clap(long, action = ArgAction::SetTrue)]
+    init_workspace_section: bool,
+
+    /// Number of context lines around each change in the --emit-patch and
+    /// --output-root-only-diff diffs
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "3",
+        env = "CARGO_EASY_DEP_DIFF_CONTEXT"
+    )]
+    diff_context: usize,
+
+    /// Discover the members from the root `workspace.members` globs instead of running
+    /// `cargo metadata`, e.g. when a member doesn't parse. Only dependencies declared with a
+    /// plain version string are hoisted in this mode
+    #[clap(global = true, long, action = ArgAction::SetTrue, conflicts_with = "metadata_cache")]
+    no_metadata: bool,
+
+    /// Cache the `cargo metadata` output in this file and reuse it until a manifest changes or a
+    /// member is added
+    #[clap(
+        global = true,
+        long,
+        value_name = "FILE",
+        env = "CARGO_EASY_DEP_METADATA_CACHE"
+    )]
+    metadata_cache: Option<PathBuf>,
+
+    /// Report the changes without writing any file
+    #[clap(long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_DRY_RUN")]
+    dry_run: bool,
+
+    /// Only print the diff of the root Cargo.toml and how many member Cargo.toml files would be
+    /// updated, without writing anything
+    #[clap(long, action = ArgAction::SetTrue, conflicts_with_all = ["output", "emit_patch"])]
+    output_root_only_diff: bool,
+
+    /// Write the changes without asking for confirmation (never asked when stdin isn't a
+    /// terminal or `CI` is set)
+    #[clap(short, long, action = ArgAction::SetTrue, env = "CARGO_EASY_DEP_YES")]
+    yes: bool,
 }
 
-type AppResult<T> = Result<T, AppError>;
+#[derive(Subcommand)]
+enum Command {
+    /// Only print the dependencies that would be hoisted, with their version requirement and
+    /// number of members, without modifying anything
+    List,
+    /// Print the shell completion script of `cargo-easy-dep`
+    Completions {
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let CargoCli::EasyDep(cli) = CargoCli::parse();
+impl Cli {
+    fn into_config(self) -> Config {
+        let only_kinds = [
+            (self.only_normal, DependencyKind::Normal),
+            (self.only_dev, DependencyKind::Development),
+            (self.only_build, DependencyKind::Build),
+        ]
+        .into_iter()
+        .filter_map(|(selected, kind)| selected.then_some(kind))
+        .collect();
+        let exclude = [
+            (DependencyKind::Normal, self.exclude_normal),
+            (DependencyKind::Development, self.exclude_dev),
+            (DependencyKind::Build, self.exclude_build),
+        ]
+        .into_iter()
+        .flat_map(|(kind, names)| names.into_iter().map(move |name| (kind, name)))
+        .collect();
+        // Only in-place rewrites from an interactive terminal are confirmed
+        let confirm = !(self.yes
+            || self.dry_run
+            || self.output_root_only_diff
+            || self.locked
+            || self.quiet
+            || self.silent
+            || self.output.is_some()
+            || self.emit_patch.is_some()
+            || self.report
+            || self.dedupe_only
+            || self.print_workspace_deps
+            || self.report_unused_workspace_deps
+            || self.command.is_some())
+            && io::stdin().is_terminal()
+            && env::var_os("CI").is_none();
 
-    match run(&cli) {
-        Ok(_) => {
-            if !cli.quiet {
-                println!(
-                    "{}",
-                    "Successfully updated all Cargo.toml files with workspace dependencies."
-                        .green(),
-                );
-            }
-            Ok(())
-        }
-        Err(e) => {
-            if !cli.quiet {
-                eprintln!("{}: {}", "Error".red(), e.to_string().red());
-            }
-            Err(e.into())
+        Config {
+            min_occurrences: self.min_occurrences,
+            threshold_percent: self.threshold_percent,
+            all: self.all,
+            workspace_roots: self.workspace_root,
+            manifest_path: self.manifest_path,
+            quiet: self.quiet || self.silent,
+            output: self.output,
+            explain: self.explain,
+            keep_version: self.keep_version,
+            only_kinds,
+            locked: self.locked,
+            warn_duplicates: self.warn_duplicates,
+            update_root: !self.no_root_update,
+            update_members: !self.no_member_update,
+            features_strategy: match (self.hoist_features, self.dedupe_features) {
+                (true, _) => FeaturesStrategy::Intersection,
+                (_, true) => FeaturesStrategy::Uniform,
+                _ => self.features_strategy,
+            },
+            hoist_path_versions: self.hoist_path_versions,
+            skip_pre_1: self.skip_pre_1,
+            allow_wildcard: self.allow_wildcard,
+            normalize_versions: self.normalize_versions,
+            group_by_kind: self.group_by_kind,
+            keep_sorted: self.keep_sorted,
+            table_style: if self.inline_tables {
+                TableStyle::Inline
+            } else if self.section_tables {
+                TableStyle::Section
+            } else {
+                TableStyle::Preserve
+            },
+            root_table_style: self.root_table_style,
+            apply_to_patch: self.apply_to_patch,
+            check_lock: self.check_lock,
+            require_kinds: self.require_kinds,
+            emit_patch: self.emit_patch,
+            trace_file: self.trace_file,
+            force: self.force,
+            max_members: self.max_members,
+            max_fragmentation: self.max_fragmentation,
+            init_workspace_section: self.init_workspace_section,
+            diff_context: self.diff_context,
+            metadata_cache: self.metadata_cache,
+            dry_run: self.dry_run,
+            root_diff: self.output_root_only_diff,
+            confirm,
+            exclude,
+            exclude_git: self.exclude_git,
+            exclude_optional: self.exclude_optional,
+            exclude_registries: self.exclude_registry,
+            prune: self.prune,
+            fix_root_spelling: self.fix_root_spelling,
+            prune_empty_tables: self.prune_empty_tables,
+            no_follow_symlinks: self.no_follow_symlinks,
+            dev_only_members: self.dev_only_members,
+            report: self.report,
+            dedupe_only: self.dedupe_only,
+            print_workspace_deps: self.print_workspace_deps,
+            report_unused: self.report_unused_workspace_deps,
+            list: matches!(self.command, Some(Command::List)),
+            format: self.format,
+            only_deps: self.deps,
+            on_conflict: self.on_conflict,
+            version_source: self.version_source,
+            no_metadata: self.no_metadata,
+            no_recurse: self.no_recurse,
+            since: self.since,
+            members_from_file: self.members_from_file,
         }
     }
 }
 
-fn run(cli: &Cli) -> AppResult<()> {
-    let workspace_path = cli
-        .workspace_root
-        .as_deref()
-        .unwrap_or_else(|| Path::new("."));
-
-    // Get cargo metadata
-    if !cli.quiet {
-        println!("{}", "Analyzing workspace...".yellow());
+fn parse_kind(kind: String) -> DependencyKind {
+    match kind.as_str() {
+        "dev" => DependencyKind::Development,
+        "build" => DependencyKind::Build,
+        _ => DependencyKind::Normal,
     }
-    let metadata = MetadataCommand::new()
-        .current_dir(workspace_path)
-        .no_deps()
-        .exec()
-        .map_err(|e| AppError::Metadata(format!("Failed to get metadata: {}", e)))?;
-
-    if !cli.quiet {
-        println!(
-            "{} {} {}",
-            "Detecting common dependencies across".yellow(),
-            metadata.workspace_members.len().to_string().yellow().bold(),
-            "workspace members...".yellow(),
-        );
+}
+
+fn parse_format(format: String) -> OutputFormat {
+    match format.as_str() {
+        "json" => OutputFormat::Json,
+        "github-annotations" => OutputFormat::GithubAnnotations,
+        _ => OutputFormat::Text,
     }
+}
 
-    // Collect dependencies used more than the minimum occurrences
-    let common_deps = find_common_dependencies(&metadata, cli.min_occurrences, cli.quiet)?;
-    if common_deps.is_empty() {
-        if !cli.quiet {
-            println!(
-                "{}",
-                "No common dependencies found across workspace members.".yellow()
-            );
-        }
-        return Ok(());
+fn parse_conflict_policy(policy: String) -> ConflictPolicy {
+    match policy.as_str() {
+        "error" => ConflictPolicy::Error,
+        "warn" => ConflictPolicy::Warn,
+        _ => ConflictPolicy::First,
     }
+}
 
-    // Update the root Cargo.toml
-    if !cli.quiet {
-        println!("{}", "Updating root Cargo.toml...".yellow());
+fn parse_ratio(ratio: &str) -> Result<f64, String> {
+    match ratio.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("'{}' is not a ratio between 0 and 1", ratio)),
     }
-    update_root_cargo_toml(&metadata, &common_deps, cli.quiet)?;
+}
 
-    // Update all member Cargo.toml files
-    if !cli.quiet {
-        println!("{}", "Updating member Cargo.toml files...".yellow());
+fn parse_features_strategy(strategy: String) -> FeaturesStrategy {
+    match strategy.as_str() {
+        "intersection" => FeaturesStrategy::Intersection,
+        "union" => FeaturesStrategy::Union,
+        "uniform" => FeaturesStrategy::Uniform,
+        _ => FeaturesStrategy::None,
     }
-    let mut updated_count = 0;
-    for package in metadata.workspace_members.iter() {
-        let pkg = metadata
-            .packages
-            .iter()
-            .find(|p| p.id == *package)
-            .ok_or_else(|| AppError::Metadata(format!("Package not found for ID: {}", package)))?;
+}
 
-        let modified = update_member_cargo_toml(&pkg.manifest_path, &common_deps, cli.quiet)?;
-        if modified {
-            updated_count += 1;
-        }
+fn parse_root_table_style(style: String) -> TableStyle {
+    match style.as_str() {
+        "expanded" => TableStyle::Section,
+        _ => TableStyle::Inline,
     }
+}
 
-    if !cli.quiet && updated_count > 0 {
-        println!(
-            "{} {} {}",
-            "Updated".green(),
-            updated_count.to_string().green().bold(),
-            "member Cargo.toml files".green()
-        );
+fn parse_version_source(source: String) -> VersionSource {
+    match source.as_str() {
+        "lockfile" => VersionSource::Lockfile,
+        _ => VersionSource::Manifest,
     }
-    Ok(())
 }
 
-fn find_common_dependencies(
-    metadata: &Metadata,
-    min_occurrences: u32,
-    quiet: bool,
-) -> AppResult<HashMap<String, Dependency>> {
-    let mut dep_count: HashMap<String, usize> = HashMap::new();
-    let mut dep_info: HashMap<String, Dependency> = HashMap::new();
-
-    // Count occurrences of each dependency and collect their info
-    for package_id in &metadata.workspace_members {
-        let package = metadata
-            .packages
-            .iter()
-            .find(|p| p.id == *package_id)
-            .ok_or_else(|| {
-                AppError::Metadata(format!("Package not found for ID: {}", package_id))
-            })?;
+fn main() -> ExitCode {
+    let cli = parse_cli();
+    if let Some(Command::Completions { shell }) = cli.command {
+        return match print_completions(shell) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(_) => ExitCode::FAILURE,
+        };
+    }
+    if cli.print_config_schema {
+        println!("{:#}", config_schema());
+        return ExitCode::SUCCESS;
+    }
+    let color = init_color(cli.color);
+    init_tracing(&cli, color);
+    let summary_line = cli.summary_line && !cli.silent;
+    let banner = !cli.no_banner;
+    let hints = !cli.no_hints;
+    let stats = cli.stats;
+    let config = cli.into_config();
 
-        for dep in package.dependencies.iter() {
-            if dep.path.is_some() {
-                continue;
+    // Nothing may follow a JSON document on stdout
+    let json = config.format == OutputFormat::Json;
+    match run(&config) {
+        Ok(summary) => {
+            if !config.is_quiet() {
+                for explanation in &summary.explanations {
+                    print_explanation(explanation, config.format);
+                }
+            }
+            if banner && !json && !config.is_quiet() && !config.is_query() {
+                match &config.emit_patch {
+                    _ if config.root_diff => println!(
+                        "{} {}",
+                        summary.members_updated.to_string().bold(),
+                        "member Cargo.toml files would be updated.".green()
+                    ),
+                    // Any needed change would have failed the run
+                    _ if config.locked => println!(
+                        "{}",
+                        "All Cargo.toml files already use workspace dependencies, nothing to update."
+                            .green()
+                    ),
+                    _ if config.dry_run => println!(
+                        "{}",
+                        "Dry run finished, no Cargo.toml file was written.".green()
+                    ),
+                    Some(patch_path) => println!(
+                        "{} {}",
+                        "Wrote the workspace dependency changes to".green(),
+                        patch_path.display().to_string().green().bold(),
+                    ),
+                    None => println!(
+                        "{}",
+                        "Successfully updated all Cargo.toml files with workspace dependencies."
+                            .green(),
+                    ),
+                }
+            }
+            // Hoisting can change the versions members resolve to
+            let rewritten = !(config.dry_run || config.root_diff || config.locked)
+                && config.emit_patch.is_none()
+                && config.output.is_none()
+                && (summary.root_modified || summary.members_updated > 0);
+            if hints && rewritten && !json && !config.is_quiet() && !config.is_query() {
+                eprintln!(
+                    "{} run `cargo check` to confirm the workspace still builds with the hoisted versions",
+                    "hint:".cyan().bold()
+                );
+            }
+            if summary_line && !json && !config.is_query() {
+                println!(
+                    "easy-dep: root={} members={}/{} deps={}",
+                    if summary.root_modified {
+                        "modified"
+                    } else {
+                        "unchanged"
+                    },
+                    summary.members_updated,
+                    summary.members,
+                    summary.common_deps,
+                );
             }
-            let count = dep_count.entry(dep.name.clone()).or_insert(0);
-            *count += 1;
-            if *count >= min_occurrences as usize {
-                // The first version occurrence will be used.
-                dep_info
-                    .entry(dep.name.clone())
-                    .or_insert_with(|| dep.clone());
+            if stats && !config.quiet && !config.is_query() {
+                print_stats(&summary.stats);
             }
+            ExitCode::SUCCESS
         }
-    }
-
-    if !quiet && !dep_info.is_empty() {
-        println!("Found {} common dependencies:", dep_info.len());
-        for (name, info) in &dep_info {
-            println!("  - {} = {}", name, info.req);
+        Err(e) => {
+            // Filtered out with --silent only
+            error!("{}: {}", "Error".red(), e.to_string().red());
+            ExitCode::FAILURE
         }
     }
-
-    Ok(dep_info)
 }
 
-fn update_root_cargo_toml(
-    metadata: &Metadata,
-    common_deps: &HashMap<String, Dependency>,
-    quiet: bool,
-) -> AppResult<bool> {
-    let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
-    let content =
-        fs::read_to_string(&root_manifest_path).map_err(|e| io_err(e, &root_manifest_path))?;
-
-    let mut doc = content
-        .parse::<DocumentMut>()
-        .map_err(|e| toml_err(e, &root_manifest_path))?;
-
-    // Ensure the workspace section exists
-    if !doc.contains_key("workspace") {
-        doc["workspace"] = toml_edit::Item::Table(toml_edit::Table::new());
+/// Parses the command line, filling the options it leaves out from the `--config` file
+fn parse_cli() -> Cli {
+    let matches = CargoCli::command().get_matches();
+    let CargoCli::EasyDep(cli) = CargoCli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (Some(path), Some(("easy-dep", matches))) = (&cli.config, matches.subcommand()) else {
+        return cli;
+    };
+    let file_args = config_file_args(path, matches).unwrap_or_else(|msg| {
+        CargoCli::command()
+            .error(ErrorKind::InvalidValue, msg)
+            .exit()
+    });
+    if file_args.is_empty() {
+        return cli;
     }
 
-    // Create or get the workspace.dependencies table
-    if !doc["workspace"]
-        .as_table()
-        .ok_or_else(|| AppError::WorkspaceUpdate("'workspace' is not a table".to_string()))?
-        .contains_key("dependencies")
-    {
-        doc["workspace"]["dependencies"] = toml_edit::Item::Table(toml_edit::Table::new());
-    }
+    // The file options go right after `easy-dep`, before any subcommand
+    let mut args: Vec<OsString> = env::args_os().collect();
+    let at = args.len().min(2);
+    args.splice(at..at, file_args);
+    let CargoCli::EasyDep(cli) = CargoCli::parse_from(args);
+    cli
+}
 
-    let mut modified = false;
+/// The command line arguments equivalent to the `--config` file options that weren't already
+/// given on the command line or through their environment variable, nor overridden by one
+/// conflicting with them
+fn config_file_args(path: &Path, matches: &ArgMatches) -> Result<Vec<OsString>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read config file '{}': {}", path.display(), e))?;
+    let doc = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| format!("failed to parse config file '{}': {}", path.display(), e))?;
 
-    // Add each common dependency to workspace.dependencies
-    for (name, info) in common_deps {
-        let deps_table = doc["workspace"]["dependencies"]
-            .as_table_mut()
+    let command = CargoCli::command();
+    let options = command
+        .find_subcommand("easy-dep")
+        .expect("the easy-dep subcommand is defined");
+    // The options given on the command line or through their environment variable
+    let given: Vec<&clap::Arg> = matches
+        .ids()
+        .filter(|id| {
+            matches!(
+                matches.value_source(id.as_str()),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        })
+        .filter_map(|id| options.get_arguments().find(|arg| arg.get_id() == id))
+        .collect();
+    let mut args = Vec::new();
+    for (key, item) in doc.iter() {
+        let name = key.replace('_', "-");
+        let arg = options
+            .get_arguments()
+            .find(|arg| {
+                arg.get_long() == Some(name.as_str())
+                    && !COMMAND_LINE_ONLY.contains(&arg.get_id().as_str())
+            })
             .ok_or_else(|| {
-                AppError::WorkspaceUpdate("'workspace.dependencies' is not a table".to_string())
+                format!(
+                    "unknown option '{}' in config file '{}'",
+                    key,
+                    path.display()
+                )
             })?;
+        // The command line wins over the file, also against the options conflicting with it
+        let conflicts = |a: &clap::Arg, b: &clap::Arg| {
+            options
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|conflict| conflict.get_id() == b.get_id())
+        };
+        if given.iter().any(|other| {
+            other.get_id() == arg.get_id() || conflicts(arg, other) || conflicts(other, arg)
+        }) {
+            continue;
+        }
 
-        // Simple version string
-        let mut dep = deps_table.entry(name).or_insert_with(|| {
-            modified = true;
-            let mut dep_table = toml_edit::Table::new();
-            dep_table.set_implicit(true);
-            dep_table["default-features"] = toml_edit::value(false);
-            dep_table["version"] = toml_edit::value(info.req.to_string());
-            dep_table.into_inline_table().into()
-        });
-
-        match &mut dep {
-            toml_edit::Item::Value(toml_edit::Value::String(_)) => {
-                // Replace with default-features = false
-                let mut dep_table = toml_edit::Table::new();
-                dep_table.set_implicit(true);
-                dep_table["default-features"] = toml_edit::value(false);
-                dep_table["version"] = toml_edit::value(info.req.to_string());
-                *dep = dep_table.into_inline_table().into();
-                modified = true;
-            }
-            toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
-                // Add default-features = false
-                let entry = table.entry("default-features").or_insert_with(|| {
-                    modified = true;
-                    toml_edit::Value::Boolean(toml_edit::Formatted::new(false))
-                });
-
-                if let Some(is_default_features) = entry.as_bool() {
-                    if !is_default_features {
-                        *entry = toml_edit::Value::Boolean(toml_edit::Formatted::new(false));
-                        modified = true;
-                    }
+        let invalid = |expected: &str| {
+            format!(
+                "'{}' in config file '{}' must be {}",
+                key,
+                path.display(),
+                expected
+            )
+        };
+        let value = |value: &toml_edit::Value| match value {
+            toml_edit::Value::String(s) => Ok(s.value().clone()),
+            toml_edit::Value::Integer(i) => Ok(i.value().to_string()),
+            toml_edit::Value::Float(f) => Ok(f.value().to_string()),
+            _ => Err(invalid("a string or a number")),
+        };
+        match (arg.get_action(), item) {
+            (ArgAction::SetTrue, item) => match item.as_bool() {
+                Some(true) => args.push(format!("--{}", name).into()),
+                Some(false) => {}
+                None => return Err(invalid("a boolean")),
+            },
+            (ArgAction::Append, toml_edit::Item::Value(toml_edit::Value::Array(values))) => {
+                for v in values {
+                    args.push(format!("--{}={}", name, value(v)?).into());
                 }
             }
-            toml_edit::Item::Table(table) => {
-                // Add default-features = false
-                let entry = table.entry("default-features").or_insert_with(|| {
-                    modified = true;
-                    toml_edit::value(true)
-                });
-
-                if let Some(is_default_features) = entry.as_bool() {
-                    if !is_default_features {
-                        *entry = toml_edit::value(false);
-                        modified = true;
-                    }
-                }
+            (ArgAction::Set | ArgAction::Append, toml_edit::Item::Value(v)) => {
+                args.push(format!("--{}={}", name, value(v)?).into());
             }
-
-            toml_edit::Item::ArrayOfTables(tables) => {
-                for table in tables.iter_mut() {
-                    modified |= update_dependencies_table(table, common_deps)?;
-                }
+            (ArgAction::Set | ArgAction::Append, _) => return Err(invalid("a value")),
+            _ => {
+                return Err(format!(
+                    "option '{}' can't be set from config file '{}'",
+                    key,
+                    path.display()
+                ));
             }
-            _ => {}
-        }
-    }
-
-    fs::write(&root_manifest_path, doc.to_string()).map_err(|e| io_err(e, &root_manifest_path))?;
-
-    if !quiet {
-        if modified {
-            println!(
-                "{} {} {}",
-                "Updated root Cargo.toml with".green(),
-                common_deps.len().to_string().green().bold(),
-                "common dependencies".green(),
-            );
-        } else {
-            println!("{}", "No changes needed for root Cargo.toml".green());
         }
     }
-
-    Ok(modified)
+    Ok(args)
 }
 
-fn update_member_cargo_toml(
-    manifest_path: &Utf8PathBuf,
-    common_deps: &HashMap<String, Dependency>,
-    quiet: bool,
-) -> AppResult<bool> {
-    let content = fs::read_to_string(manifest_path).map_err(|e| io_err(e, manifest_path))?;
-
-    let mut doc = content
-        .parse::<DocumentMut>()
-        .map_err(|e| toml_err(e, manifest_path))?;
-
-    let mut modified = false;
-
-    // Update regular dependencies
-    if let Some(deps) = doc.get_mut("dependencies") {
-        if let Some(deps_table) = deps.as_table_mut() {
-            modified |= update_dependencies_table(deps_table, common_deps)?;
-        } else {
-            return Err(AppError::MemberUpdate(
-                "'dependencies' is not a table".to_string(),
-                manifest_path.to_path_buf(),
-            ));
+/// The JSON Schema of the `--config` file, derived from the options `config_file_args` accepts
+fn config_schema() -> serde_json::Value {
+    let command = CargoCli::command();
+    let options = command
+        .find_subcommand("easy-dep")
+        .expect("the easy-dep subcommand is defined");
+    let mut properties = serde_json::Map::new();
+    for arg in options.get_arguments() {
+        let Some(name) = arg.get_long() else {
+            continue;
+        };
+        if arg.is_hide_set() || COMMAND_LINE_ONLY.contains(&arg.get_id().as_str()) {
+            continue;
         }
-    }
-
-    // Update dev-dependencies
-    if let Some(deps) = doc.get_mut("dev-dependencies") {
-        if let Some(deps_table) = deps.as_table_mut() {
-            modified |= update_dependencies_table(deps_table, common_deps)?;
+        let possible_values: Vec<String> = arg
+            .get_possible_values()
+            .iter()
+            .map(|value| value.get_name().to_string())
+            .collect();
+        let value = if possible_values.is_empty() {
+            serde_json::json!({ "type": ["string", "number"] })
         } else {
-            return Err(AppError::MemberUpdate(
-                "'dev-dependencies' is not a table".to_string(),
-                manifest_path.to_path_buf(),
-            ));
+            serde_json::json!({ "type": "string", "enum": possible_values })
+        };
+        let mut schema = match arg.get_action() {
+            ArgAction::SetTrue => serde_json::json!({ "type": "boolean" }),
+            ArgAction::Set => value,
+            // A single value is accepted as well as an array
+            ArgAction::Append => serde_json::json!({
+                "anyOf": [value, { "type": "array", "items": value }]
+            }),
+            _ => continue,
+        };
+        if let Some(help) = arg.get_long_help().or_else(|| arg.get_help()) {
+            schema["description"] = help.to_string().into();
         }
+        properties.insert(name.to_string(), schema);
     }
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "cargo-easy-dep configuration",
+        "description": "Default options of `cargo easy-dep --config <FILE>`, keyed by long option name",
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
 
-    // Update build-dependencies
-    if let Some(deps) = doc.get_mut("build-dependencies") {
-        if let Some(deps_table) = deps.as_table_mut() {
-            modified |= update_dependencies_table(deps_table, common_deps)?;
-        } else {
-            return Err(AppError::MemberUpdate(
-                "'build-dependencies' is not a table".to_string(),
-                manifest_path.to_path_buf(),
-            ));
-        }
-    }
+/// Completes the `cargo-easy-dep easy-dep` invocation of the binary, completing
+/// `cargo easy-dep` is up to cargo's own completion script
+fn print_completions(shell: Shell) -> io::Result<()> {
+    let mut script = Vec::new();
+    clap_complete::generate(
+        shell,
+        &mut CargoCli::command(),
+        "cargo-easy-dep",
+        &mut script,
+    );
+    io::stdout().write_all(&script)
+}
 
-    if modified {
-        fs::write(manifest_path, doc.to_string()).map_err(|e| io_err(e, manifest_path))?;
-        if !quiet {
-            println!("  - Updated member at: {}", manifest_path);
+/// Prints why each dependency of a workspace was or wasn't hoisted
+fn print_explanation(explanation: &Explanation, format: OutputFormat) {
+    match format {
+        OutputFormat::Text | OutputFormat::GithubAnnotations => {
+            println!("Dependency decisions:");
+            for decision in &explanation.decisions {
+                println!("  - {}: {}", decision.name, decision.description);
+            }
         }
-    } else if !quiet {
-        println!("  - No changes needed for: {}", manifest_path);
+        OutputFormat::Json => println!("{}", serde_json::json!(explanation)),
     }
-
-    Ok(modified)
 }
 
-fn update_dependencies_table(
-    deps_table: &mut toml_edit::Table,
-    common_deps: &HashMap<String, Dependency>,
-) -> AppResult<bool> {
-    let mut modified = false;
-
-    for name in common_deps.keys() {
-        if deps_table.contains_key(name) {
-            match &mut deps_table[name] {
-                toml_edit::Item::Value(toml_edit::Value::String(_)) => {
-                    // Replace with workspace = true
-                    let mut dep_table = toml_edit::Table::new();
-                    dep_table.set_implicit(true);
-                    dep_table["workspace"] = toml_edit::value(true);
-                    dep_table["default-features"] = toml_edit::value(true);
-                    deps_table[name] = dep_table.into_inline_table().into();
-                    modified = true;
-                }
-                toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
-                    // Keep existing configuration but add workspace = true
-                    // Remove the version field if it exists
-                    if table.contains_key("version") {
-                        table.remove("version");
-                    }
-                    // Add workspace = true
-                    let entry = table.entry("workspace").or_insert_with(|| {
-                        modified = true;
-                        toml_edit::Value::Boolean(toml_edit::Formatted::new(true))
-                    });
-
-                    if let Some(is_workspace) = entry.as_bool() {
-                        if !is_workspace {
-                            *entry = toml_edit::Value::Boolean(toml_edit::Formatted::new(true));
-                            modified = true;
-                        }
-                    }
-
-                    // Add implicitly define default-features
-                    table.entry("default-features").or_insert_with(|| {
-                        modified = true;
-                        toml_edit::Value::Boolean(toml_edit::Formatted::new(true))
-                    });
-                }
-                toml_edit::Item::Table(table) => {
-                    // Keep existing configuration but add workspace = true
-                    // Remove the version field if it exists
-                    if table.contains_key("version") {
-                        table.remove("version");
-                    }
-                    // Add workspace = true
-                    let entry = table.entry("workspace").or_insert_with(|| {
-                        modified = true;
-                        toml_edit::value(true)
-                    });
-
-                    if let Some(is_workspace) = entry.as_bool() {
-                        if !is_workspace {
-                            *entry = toml_edit::value(true);
-                            modified = true;
-                        }
-                    }
-
-                    // Add default-features = info.uses_default_features
-                    table.entry("default-features").or_insert_with(|| {
-                        modified = true;
-                        toml_edit::value(true)
-                    });
-                }
+fn print_stats(stats: &RunStats) {
+    eprintln!("{}", "Stats:".bold());
+    for (phase, duration) in [
+        ("metadata", stats.metadata),
+        ("analysis", stats.analysis),
+        ("root update", stats.root_update),
+        ("member updates", stats.member_updates),
+    ] {
+        eprintln!("  {:<16} {:>10.1?}", phase, duration);
+    }
+    eprintln!("  {:<16} {:>10}", "files parsed", stats.files_parsed);
+    eprintln!("  {:<16} {:>10}", "bytes rewritten", stats.bytes_written);
+}
 
-                toml_edit::Item::ArrayOfTables(tables) => {
-                    for table in tables.iter_mut() {
-                        modified |= update_dependencies_table(table, common_deps)?;
-                    }
-                }
-                _ => {}
+/// Decides whether output is colored, following cargo's `--color` and the `NO_COLOR`/`CLICOLOR`
+/// conventions
+fn init_color(choice: ColorChoice) -> bool {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            let is_set = |name| env::var_os(name).is_some_and(|value| !value.is_empty());
+            let is_zero = |name| env::var_os(name).is_some_and(|value| value == "0");
+            if is_set("NO_COLOR") {
+                false
+            } else if is_set("CLICOLOR_FORCE") && !is_zero("CLICOLOR_FORCE") {
+                true
+            } else {
+                // Results go to stdout and diagnostics to stderr, both must be terminals
+                !is_zero("CLICOLOR") && io::stdout().is_terminal() && io::stderr().is_terminal()
             }
         }
-    }
+    };
+    colored::control::set_override(enabled);
+    enabled
+}
+
+/// Routes diagnostics to stderr, filtered by `RUST_LOG` or the verbosity flags
+fn init_tracing(cli: &Cli, color: bool) {
+    let filter = if cli.silent {
+        EnvFilter::new("off")
+    } else if cli.quiet {
+        EnvFilter::new(format!("{}=error", env!("CARGO_CRATE_NAME")))
+    } else {
+        let level = match cli.verbose {
+            0 => "info",
+            1 => "debug",
+            _ => "trace",
+        };
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(format!("{}={}", env!("CARGO_CRATE_NAME"), level)))
+    };
 
-    Ok(modified)
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(color)
+        // The colors come from `colored`, so escape codes must reach the terminal untouched
+        .with_ansi_sanitization(!color)
+        .without_time()
+        .with_target(false)
+        .with_level(false)
+        .init();
 }
@@ -0,0 +1,221 @@
+use crate::{
+    AppError, AppResult, Config, discovery::toml_value_to_json, read_manifest, relative_path,
+};
+use cargo_metadata::Metadata;
+use colored::Colorize;
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+use toml_edit::DocumentMut;
+use tracing::{debug, warn};
+
+/// A `[patch.<source>]` entry declared identically by enough members to be moved to the root,
+/// where cargo actually applies it
+#[derive(Debug)]
+pub(crate) struct SharedPatch {
+    /// Source the patch applies to, e.g. `crates-io` or a git URL
+    pub(crate) source: String,
+    /// Name of the patched crate
+    pub(crate) name: String,
+    /// The patch, as written to the root, its `path` relative to the root
+    pub(crate) value: toml_edit::Value,
+    /// Number of members declaring it
+    pub(crate) occurrences: usize,
+}
+
+/// Finds the `[patch]` entries enough members declare identically, leaving out the ones the root
+/// already patches differently
+pub(crate) fn shared_patches(metadata: &Metadata, config: &Config) -> AppResult<Vec<SharedPatch>> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let root_manifest_path = metadata.workspace_root.join("Cargo.toml");
+    let (_, root) = read_manifest(&root_manifest_path)?;
+
+    let members = metadata.workspace_packages();
+    let mut declared: BTreeMap<(String, String), Vec<toml_edit::Value>> = BTreeMap::new();
+    for package in &members {
+        // The root's own patches are the ones cargo applies
+        if package.manifest_path == root_manifest_path {
+            continue;
+        }
+        // Unreadable members are reported when they're updated
+        let Ok((_, doc)) = read_manifest(&package.manifest_path) else {
+            continue;
+        };
+        let member_dir = package
+            .manifest_path
+            .parent()
+            .map_or(workspace_root, |dir| dir.as_std_path());
+        for (source, entries) in patch_tables(&doc) {
+            for (name, item) in entries.iter() {
+                if let Some(mut value) = inline_value(item) {
+                    rebase_path(&mut value, member_dir, workspace_root);
+                    declared
+                        .entry((source.to_string(), name.to_string()))
+                        .or_default()
+                        .push(value);
+                }
+            }
+        }
+    }
+
+    let min_occurrences = config.required_occurrences(members.len()) as usize;
+    let mut shared = Vec::new();
+    for ((source, name), values) in declared {
+        let value = &values[0];
+        let json = toml_value_to_json(value);
+        if values.iter().any(|other| toml_value_to_json(other) != json) {
+            debug!(
+                "'{}' in [patch.{}] differs between members, not moving it",
+                name, source
+            );
+            continue;
+        }
+        if values.len() < min_occurrences {
+            continue;
+        }
+        let existing = root
+            .get("patch")
+            .and_then(|patch| patch.get(&source))
+            .and_then(|entries| entries.get(&name))
+            .and_then(inline_value)
+            .map(|mut existing| {
+                rebase_path(&mut existing, workspace_root, workspace_root);
+                existing
+            });
+        if existing.is_some_and(|existing| toml_value_to_json(&existing) != json) {
+            warn!(
+                "{} not moving '{}' in [patch.{}]: the root already patches it differently",
+                "Warning:".yellow().bold(),
+                name,
+                source
+            );
+            continue;
+        }
+        shared.push(SharedPatch {
+            source,
+            name,
+            value: value.clone(),
+            occurrences: values.len(),
+        });
+    }
+    Ok(shared)
+}
+
+/// Adds the shared patches the root doesn't declare yet, returning whether any was added
+pub(crate) fn hoist_patches(doc: &mut DocumentMut, patches: &[SharedPatch]) -> AppResult<bool> {
+    let mut modified = false;
+    for patch in patches {
+        let patch_table = doc
+            .entry("patch")
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            })
+            .as_table_like_mut()
+            .ok_or_else(|| AppError::WorkspaceUpdate("'patch' is not a table".to_string()))?;
+        let entries = patch_table
+            .entry(&patch.source)
+            .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| {
+                AppError::WorkspaceUpdate(format!("'patch.{}' is not a table", patch.source))
+            })?;
+        if !entries.contains_key(&patch.name) {
+            entries.insert(&patch.name, toml_edit::Item::Value(patch.value.clone()));
+            modified = true;
+        }
+    }
+    Ok(modified)
+}
+
+/// Removes the shared patches from a member, dropping the tables they leave empty, returning
+/// whether any was removed
+pub(crate) fn remove_patches(doc: &mut DocumentMut, patches: &[SharedPatch]) -> bool {
+    let mut modified = false;
+    for patch in patches {
+        let Some(patch_table) = doc
+            .get_mut("patch")
+            .and_then(|patch| patch.as_table_like_mut())
+        else {
+            break;
+        };
+        if let Some(entries) = patch_table
+            .get_mut(&patch.source)
+            .and_then(|entries| entries.as_table_like_mut())
+        {
+            modified |= entries.remove(&patch.name).is_some();
+            if entries.is_empty() {
+                patch_table.remove(&patch.source);
+            }
+        }
+        if patch_table.is_empty() {
+            doc.remove("patch");
+        }
+    }
+    modified
+}
+
+/// The `[patch.<source>]` tables of a manifest along with their source
+fn patch_tables(doc: &DocumentMut) -> Vec<(&str, &dyn toml_edit::TableLike)> {
+    doc.get("patch")
+        .and_then(|patch| patch.as_table_like())
+        .map(|patch| {
+            patch
+                .iter()
+                .filter_map(|(source, entries)| Some((source, entries.as_table_like()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A patch entry as an inline value, whether it's written inline or as a section
+fn inline_value(item: &toml_edit::Item) -> Option<toml_edit::Value> {
+    let mut value = match item {
+        toml_edit::Item::Value(value) => value.clone(),
+        toml_edit::Item::Table(table) => {
+            toml_edit::Value::InlineTable(table.clone().into_inline_table())
+        }
+        _ => return None,
+    };
+    value.decor_mut().clear();
+    Some(value)
+}
+
+/// Rewrites the `path` of a patch declared in the manifest of `manifest_dir` relative to the
+/// `workspace_root`, so patches written from different members compare by the crate they point
+/// to and keep pointing to it once moved to the root
+fn rebase_path(value: &mut toml_edit::Value, manifest_dir: &Path, workspace_root: &Path) {
+    let Some(table) = value.as_inline_table_mut() else {
+        return;
+    };
+    let Some(path) = table.get("path").and_then(|path| path.as_str()) else {
+        return;
+    };
+    if Path::new(path).is_absolute() {
+        return;
+    }
+    let resolved = normalize_path(&manifest_dir.join(path));
+    let relative = relative_path(workspace_root, &resolved);
+    let relative = match relative.to_str() {
+        Some("") => ".".to_string(),
+        Some(relative) => relative.replace('\\', "/"),
+        None => return,
+    };
+    table.insert("path", relative.into());
+    table.fmt();
+}
+
+/// `path` with its `.` and `..` components folded, without touching the file system
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.pop() => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
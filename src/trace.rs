@@ -0,0 +1,98 @@
+use crate::{AppResult, error::io_err};
+use std::{
+    cell::RefCell,
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+thread_local! {
+    /// The `--trace-file` of the run in progress, along with its path
+    static TRACE: RefCell<Option<(fs::File, PathBuf)>> = const { RefCell::new(None) };
+}
+
+/// Keeps recording to the trace file until dropped
+pub(crate) struct TraceGuard {
+    installed: bool,
+}
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        if self.installed {
+            TRACE.set(None);
+        }
+    }
+}
+
+/// Starts appending to the trace file at `path`, unless a trace is already being recorded, e.g.
+/// by `run` around `run_workspace`
+pub(crate) fn start(path: Option<&Path>) -> AppResult<TraceGuard> {
+    let Some(path) = path.filter(|_| TRACE.with_borrow(Option::is_none)) else {
+        return Ok(TraceGuard { installed: false });
+    };
+    let file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .map_err(|e| io_err(e, path))?;
+    TRACE.set(Some((file, path.to_path_buf())));
+    Ok(TraceGuard { installed: true })
+}
+
+/// Records `path` being read
+pub(crate) fn read(path: &Path) -> AppResult<()> {
+    record(format_args!("read {}", path.display()))
+}
+
+/// Records `path` being written, `modified` telling whether its content changed
+pub(crate) fn write(path: &Path, modified: bool) -> AppResult<()> {
+    record(format_args!(
+        "write {} {}",
+        path.display(),
+        if modified { "modified" } else { "unchanged" }
+    ))
+}
+
+fn record(event: fmt::Arguments<'_>) -> AppResult<()> {
+    TRACE.with_borrow_mut(|trace| {
+        let Some((file, trace_path)) = trace else {
+            return Ok(());
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        writeln!(file, "{} {}", timestamp(now), event).map_err(|e| io_err(e, &*trace_path))
+    })
+}
+
+/// The RFC 3339 UTC timestamp of a time `since_epoch`, converting days to a civil date as in
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn timestamp(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs();
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let time = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60,
+        since_epoch.subsec_millis()
+    )
+}
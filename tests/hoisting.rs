@@ -0,0 +1,2646 @@
+use cargo_easy_dep::{
+    AppError, Config, ConflictPolicy, FeaturesStrategy, TableStyle, VersionSource, dedupe_report,
+    find_common_dependencies, lock_changes, report_workspace, run, run_workspace,
+    workspace_dependencies_snippet,
+};
+use cargo_metadata::DependencyKind;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use toml_edit::DocumentMut;
+
+static FIXTURE_COPIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Copies `tests/fixtures/<name>` to a fresh temporary directory so it can be rewritten
+fn fixture(name: &str) -> PathBuf {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let dst = std::env::temp_dir().join(format!(
+        "cargo-easy-dep-{}-{}-{}",
+        name,
+        std::process::id(),
+        FIXTURE_COPIES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dst);
+    copy_dir(&src, &dst);
+    dst
+}
+
+fn copy_dir(src: &Path, dst: &Path) {
+    fs::create_dir_all(dst).unwrap();
+    for entry in fs::read_dir(src).unwrap() {
+        let entry = entry.unwrap();
+        let target = dst.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+fn manifest(path: &Path) -> DocumentMut {
+    fs::read_to_string(path).unwrap().parse().unwrap()
+}
+
+fn config(root: &Path) -> Config {
+    Config {
+        workspace_roots: vec![root.to_path_buf()],
+        quiet: true,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn section_form_dependency_is_preserved() {
+    let root = fixture("section-form");
+    run_workspace(&config(&root), &root).unwrap();
+
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(
+        member.contains(
+            "[dependencies.serde]\nfeatures = [\"derive\"]\nworkspace = true\ndefault-features = true\n"
+        ),
+        "section form was not preserved:\n{member}"
+    );
+    assert!(
+        !member.contains("version = \"1\""),
+        "version was kept:\n{member}"
+    );
+
+    let inline = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    assert!(
+        inline.contains("serde = { workspace = true, default-features = true }"),
+        "inline form was changed:\n{inline}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn emit_patch_leaves_manifests_untouched() {
+    let root = fixture("section-form");
+    let patch_path = root.join("easy-dep.patch");
+    let config = Config {
+        emit_patch: Some(patch_path.clone()),
+        ..config(&root)
+    };
+    let member_before = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    cargo_easy_dep::run(&config).unwrap();
+
+    let member_after = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert_eq!(member_before, member_after);
+
+    let patch = fs::read_to_string(&patch_path).unwrap();
+    assert!(
+        patch.contains("-version = \"1\"\n"),
+        "missing member change:\n{patch}"
+    );
+    assert!(
+        patch.contains("+workspace = true\n"),
+        "missing member change:\n{patch}"
+    );
+    assert!(
+        patch.contains("+[workspace.dependencies]\n"),
+        "missing root change:\n{patch}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn emitted_patch_applies_from_the_repository_root() {
+    let root = fixture("section-form");
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .current_dir(&root)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+
+    // The tests run from this crate, outside the fixture's repository
+    let patch_path = root.join("easy-dep.patch");
+    let config = Config {
+        emit_patch: Some(patch_path.clone()),
+        ..config(&root)
+    };
+    cargo_easy_dep::run(&config).unwrap();
+    git(&["apply", "easy-dep.patch"]);
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert!(
+        workspace["workspace"]["dependencies"]
+            .get("serde")
+            .is_some()
+    );
+    let member = manifest(&root.join("a/Cargo.toml"));
+    assert_eq!(
+        member["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn mixed_default_features_are_enabled_per_member() {
+    let root = fixture("mixed-defaults");
+    run_workspace(&config(&root), &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["workspace"]["dependencies"]["clap"]["default-features"].as_bool(),
+        Some(false)
+    );
+    let member = |name: &str| fs::read_to_string(root.join(name).join("Cargo.toml")).unwrap();
+    assert!(
+        member("a").contains("clap = { workspace = true, default-features = false }"),
+        "opted out member changed:\n{}",
+        member("a")
+    );
+    assert!(
+        member("b").contains("clap = { workspace = true, features = [\"default\"] }"),
+        "default features not enabled:\n{}",
+        member("b")
+    );
+    assert!(
+        member("c").contains("clap = { workspace = true, features = [\"derive\", \"default\"] }"),
+        "default features not enabled:\n{}",
+        member("c")
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn inherited_members_keep_default_features() {
+    let root = fixture("inherited-defaults");
+    run_workspace(&config(&root), &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["workspace"]["dependencies"]["serde"]["default-features"].as_bool(),
+        Some(false)
+    );
+    // Members disagree on the default features, so the inheriting one enables them as a feature
+    let inherited = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(
+        inherited.contains("serde = { workspace = true, features = [\"default\"] }"),
+        "default features not kept:\n{inherited}"
+    );
+    let opted_out = manifest(&root.join("b/Cargo.toml"));
+    assert_eq!(
+        opted_out["dependencies"]["serde"]["default-features"].as_bool(),
+        Some(false)
+    );
+
+    // A second run has nothing left to change
+    let config = Config {
+        locked: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn prune_removes_unused_workspace_dependencies() {
+    let root = fixture("prune");
+    let config = Config {
+        prune: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert!(deps.contains_key("serde"));
+    assert!(!deps.contains_key("rand"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn prune_keeps_entries_inherited_by_unlisted_members() {
+    let root = fixture("prune");
+    let inheriting = root.join("b/Cargo.toml");
+    let mut member = fs::read_to_string(&inheriting).unwrap();
+    member.push_str("rand = { workspace = true }\n");
+    fs::write(&inheriting, member).unwrap();
+    let members_path = root.join("members.txt");
+    fs::write(&members_path, "a\n").unwrap();
+
+    let config = Config {
+        prune: true,
+        members_from_file: Some(members_path),
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    // b isn't listed, but still inherits rand
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert!(deps.contains_key("serde"));
+    assert!(deps.contains_key("rand"));
+    cargo_metadata::MetadataCommand::new()
+        .current_dir(&root)
+        .no_deps()
+        .exec()
+        .unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn prune_keeps_target_specific_inherited_entries_without_metadata() {
+    let root = fixture("prune");
+    let inheriting = root.join("b/Cargo.toml");
+    let mut member = fs::read_to_string(&inheriting).unwrap();
+    member.push_str("\n[target.'cfg(unix)'.dependencies]\nrand = { workspace = true }\n");
+    fs::write(&inheriting, member).unwrap();
+
+    // Discovered members don't list their target-specific dependencies
+    let config = Config {
+        prune: true,
+        no_metadata: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert!(deps.contains_key("serde"));
+    assert!(deps.contains_key("rand"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn root_string_entry_is_promoted_with_hoisted_features() {
+    let root = fixture("root-string-entry");
+    let config = Config {
+        features_strategy: FeaturesStrategy::Intersection,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let serde = &workspace["workspace"]["dependencies"]["serde"];
+    assert_eq!(serde["version"].as_str(), Some("1.0.100"));
+    assert_eq!(serde["default-features"].as_bool(), Some(false));
+    let features = serde["features"].as_array().unwrap();
+    assert_eq!(
+        features
+            .iter()
+            .map(|f| f.as_str().unwrap())
+            .collect::<Vec<_>>(),
+        ["derive"]
+    );
+    let member = manifest(&root.join("a/Cargo.toml"));
+    assert!(member["dependencies"]["serde"].get("features").is_none());
+
+    // A second run has nothing left to change
+    let config = Config {
+        locked: true,
+        ..config
+    };
+    run_workspace(&config, &root).unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn member_outside_workspace_root_is_updated() {
+    let root = fixture("outside-root");
+    let workspace_root = root.join("ws");
+    run_workspace(&config(&workspace_root), &workspace_root).unwrap();
+
+    let workspace = manifest(&workspace_root.join("Cargo.toml"));
+    assert!(
+        workspace["workspace"]["dependencies"]
+            .get("serde")
+            .is_some()
+    );
+    let external = manifest(&root.join("external/Cargo.toml"));
+    let serde = &external["dependencies"]["serde"];
+    assert_eq!(serde["workspace"].as_bool(), Some(true));
+    assert!(serde.get("version").is_none());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn no_metadata_discovers_members_from_globs() {
+    let root = fixture("no-metadata");
+    let config = Config {
+        no_metadata: true,
+        ..config(&root)
+    };
+    let summary = run_workspace(&config, &root).unwrap();
+    assert_eq!(summary.members, 2);
+    assert_eq!(summary.members_updated, 2);
+
+    for member in ["a", "b"] {
+        let manifest = manifest(&root.join("crates").join(member).join("Cargo.toml"));
+        assert_eq!(
+            manifest["dependencies"]["serde"]["workspace"].as_bool(),
+            Some(true)
+        );
+    }
+    let excluded = fs::read_to_string(root.join("crates/skip/Cargo.toml")).unwrap();
+    assert!(excluded.contains("serde = \"1\""));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn nested_workspace_leaves_parent_untouched() {
+    let root = fixture("nested");
+    let inner = root.join("inner");
+    let outer_before = fs::read_to_string(root.join("outer-a/Cargo.toml")).unwrap();
+    let config = Config {
+        no_recurse: true,
+        ..config(&inner)
+    };
+    let summary = run_workspace(&config, &inner).unwrap();
+    assert_eq!(summary.members_updated, 2);
+
+    let workspace = manifest(&inner.join("Cargo.toml"));
+    assert!(
+        workspace["workspace"]["dependencies"]
+            .get("serde")
+            .is_some()
+    );
+    assert!(
+        !manifest(&root.join("Cargo.toml"))["workspace"]
+            .as_table()
+            .unwrap()
+            .contains_key("dependencies")
+    );
+    let outer_after = fs::read_to_string(root.join("outer-a/Cargo.toml")).unwrap();
+    assert_eq!(outer_before, outer_after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn no_recurse_rejects_a_member_directory() {
+    let root = fixture("nested");
+    let member = root.join("outer-a");
+    let config = Config {
+        no_recurse: true,
+        ..config(&member)
+    };
+    let err = run_workspace(&config, &member).unwrap_err();
+    assert!(matches!(
+        err,
+        cargo_easy_dep::AppError::NotWorkspaceRoot(..)
+    ));
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert!(
+        !workspace["workspace"]
+            .as_table()
+            .unwrap()
+            .contains_key("dependencies")
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn inline_table_puts_workspace_first() {
+    let root = fixture("inline-table");
+    run_workspace(&config(&root), &root).unwrap();
+
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(
+        member.contains(
+            "serde = { workspace = true, features = [\"derive\"], default-features = true }\n"
+        ),
+        "unexpected inline table:\n{member}"
+    );
+    let compact = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    assert!(
+        compact
+            .contains("serde = { workspace = true, optional = true, default-features = true }\n"),
+        "unexpected inline table:\n{compact}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn inline_dependencies_table_is_updated() {
+    let root = fixture("root-inline-dependencies");
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    assert_eq!(summary.common_deps, 2);
+    assert_eq!(summary.members_updated, 2);
+
+    let member = manifest(&root.join("a/Cargo.toml"));
+    let deps = member["dependencies"].as_inline_table().unwrap();
+    for name in ["serde", "itoa"] {
+        let dep = deps[name].as_inline_table().unwrap();
+        assert_eq!(dep.get("workspace").and_then(|w| w.as_bool()), Some(true));
+        assert!(dep.get("version").is_none(), "{name} kept its version");
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn line_endings_and_final_newline_are_preserved() {
+    let root = fixture("crlf");
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    assert_eq!(summary.members_updated, 2);
+
+    let crlf = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(
+        crlf.contains("workspace = true"),
+        "a wasn't rewritten:\n{crlf}"
+    );
+    assert!(crlf.ends_with("\r\n"));
+    assert_eq!(
+        crlf.matches('\n').count(),
+        crlf.matches("\r\n").count(),
+        "bare LF in a CRLF manifest: {crlf:?}"
+    );
+
+    let unterminated = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    assert!(
+        unterminated.contains("workspace = true"),
+        "b wasn't rewritten:\n{unterminated}"
+    );
+    assert!(!unterminated.contains('\r'));
+    assert!(
+        !unterminated.ends_with('\n'),
+        "final newline added: {unterminated:?}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn path_dependencies_are_skipped_by_default() {
+    let root = fixture("path-version");
+    let before = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    assert_eq!(summary.common_deps, 0);
+
+    let after = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert_eq!(before, after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn path_version_dependency_is_hoisted_with_its_path() {
+    let root = fixture("path-version");
+    let config = Config {
+        hoist_path_versions: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let c = &workspace["workspace"]["dependencies"]["c"];
+    assert_eq!(c["path"].as_str(), Some("c"));
+    assert_eq!(c["version"].as_str(), Some("^0.1.0"));
+    for member in ["a", "b"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        let c = &member["dependencies"]["c"];
+        assert_eq!(c["workspace"].as_bool(), Some(true));
+        assert!(c.get("path").is_none());
+        assert!(c.get("version").is_none());
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn unused_workspace_dependencies_are_reported() {
+    let root = fixture("prune");
+    let unused = cargo_easy_dep::unused_workspace_dependencies(&config(&root), &root).unwrap();
+    assert_eq!(unused, ["rand"]);
+
+    let config = Config {
+        report_unused: true,
+        locked: true,
+        ..config(&root)
+    };
+    assert!(matches!(
+        cargo_easy_dep::run(&config),
+        Err(cargo_easy_dep::AppError::UnusedWorkspaceDependencies(names)) if names == ["rand"]
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn unused_workspace_dependencies_are_reported_for_every_member() {
+    let root = fixture("prune");
+    let inheriting = root.join("b/Cargo.toml");
+    let mut member = fs::read_to_string(&inheriting).unwrap();
+    member.push_str("rand = { workspace = true }\n");
+    fs::write(&inheriting, member).unwrap();
+    let members_path = root.join("members.txt");
+    fs::write(&members_path, "a\n").unwrap();
+
+    // Only a is selected, but b inherits rand
+    let config = Config {
+        members_from_file: Some(members_path),
+        ..config(&root)
+    };
+    let unused = cargo_easy_dep::unused_workspace_dependencies(&config, &root).unwrap();
+    assert!(unused.is_empty(), "reported as unused: {unused:?}");
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn excluded_member_is_neither_counted_nor_updated() {
+    for no_metadata in [false, true] {
+        let root = fixture("excluded-member");
+        let legacy_before = fs::read_to_string(root.join("crates/legacy/Cargo.toml")).unwrap();
+        let config = Config {
+            no_metadata,
+            ..config(&root)
+        };
+        let summary = run_workspace(&config, &root).unwrap();
+        assert_eq!(summary.members, 2);
+
+        let workspace = manifest(&root.join("Cargo.toml"));
+        let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+        assert!(deps.contains_key("serde"));
+        assert!(!deps.contains_key("itoa"));
+        let legacy_after = fs::read_to_string(root.join("crates/legacy/Cargo.toml")).unwrap();
+        assert_eq!(legacy_before, legacy_after);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
+
+#[test]
+fn max_members_stops_before_writing() {
+    let root = fixture("section-form");
+    let member_before = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    let config = Config {
+        max_members: Some(1),
+        ..config(&root)
+    };
+    assert!(matches!(
+        run_workspace(&config, &root),
+        Err(cargo_easy_dep::AppError::TooManyMembers(2, 1))
+    ));
+    let member_after = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert_eq!(member_before, member_after);
+
+    let config = Config {
+        force: true,
+        ..config
+    };
+    assert_eq!(run_workspace(&config, &root).unwrap().members_updated, 2);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn normalize_versions_writes_the_shortest_requirement() {
+    let root = fixture("normalize-versions");
+    let config = Config {
+        normalize_versions: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = &workspace["workspace"]["dependencies"];
+    assert_eq!(deps["serde"]["version"].as_str(), Some("1"));
+    assert_eq!(deps["toml"]["version"].as_str(), Some("0.8"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn list_reports_common_dependencies_without_writing() {
+    let root = fixture("section-form");
+    let member_before = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    let common_deps = cargo_easy_dep::list_workspace(&config(&root), &root).unwrap();
+    assert_eq!(common_deps.len(), 1);
+    assert_eq!(common_deps[0].dep.name, "serde");
+    assert_eq!(common_deps[0].occurrences, 2);
+
+    let member_after = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert_eq!(member_before, member_after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn dev_only_members_inherit_without_voting() {
+    // serde is used by a, b and examples/demo, itoa only by a and examples/demo
+    let root = fixture("dev-only");
+    assert_eq!(hoisted_names(&config(&root), &root), ["itoa", "serde"]);
+    let config = Config {
+        dev_only_members: vec![PathBuf::from("examples")],
+        ..config(&root)
+    };
+    assert_eq!(hoisted_names(&config, &root), ["serde"]);
+    run_workspace(&config, &root).unwrap();
+
+    let demo = manifest(&root.join("examples/demo/Cargo.toml"));
+    assert_eq!(
+        demo["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+    assert_eq!(demo["dependencies"]["itoa"].as_str(), Some("1"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn member_opting_out_is_left_untouched() {
+    let root = fixture("member-opt-out");
+    let opted_out_before = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    // b still counts towards serde's threshold, it's only never rewritten
+    assert_eq!(summary.common_deps, 1);
+    assert_eq!(summary.members_updated, 1);
+
+    let member = manifest(&root.join("a/Cargo.toml"));
+    assert_eq!(
+        member["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+    let opted_out_after = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    assert_eq!(opted_out_before, opted_out_after);
+
+    // Not even for the dependencies only it declares
+    let mut opted_out = opted_out_before.clone();
+    opted_out.push_str("itoa = \"1\"\n");
+    fs::write(root.join("b/Cargo.toml"), &opted_out).unwrap();
+    let all = Config {
+        all: true,
+        ..config(&root)
+    };
+    run_workspace(&all, &root).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("b/Cargo.toml")).unwrap(),
+        opted_out
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn since_only_considers_changed_members() {
+    let root = fixture("section-form");
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .current_dir(&root)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+    fs::write(root.join("b/src/lib.rs"), "pub fn changed() {}\n").unwrap();
+
+    let unchanged_before = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    let config = Config {
+        since: Some("HEAD".to_string()),
+        min_occurrences: 1,
+        ..config(&root)
+    };
+    let summary = run_workspace(&config, &root).unwrap();
+    assert_eq!(summary.members, 1);
+    assert_eq!(summary.members_updated, 1);
+
+    let changed = manifest(&root.join("b/Cargo.toml"));
+    assert_eq!(
+        changed["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+    let unchanged_after = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert_eq!(unchanged_before, unchanged_after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn since_keeps_entries_unchanged_members_inherit() {
+    let root = fixture("prune");
+    let inheriting = root.join("b/Cargo.toml");
+    let mut member = fs::read_to_string(&inheriting).unwrap();
+    member.push_str("rand = { workspace = true }\n");
+    fs::write(&inheriting, member).unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .current_dir(&root)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+    fs::write(root.join("a/src/lib.rs"), "pub fn changed() {}\n").unwrap();
+
+    // Only a changed, b still inherits rand
+    let config = Config {
+        since: Some("HEAD".to_string()),
+        prune: true,
+        ..config(&root)
+    };
+    let unused = cargo_easy_dep::unused_workspace_dependencies(&config, &root).unwrap();
+    assert!(unused.is_empty(), "reported as unused: {unused:?}");
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert!(deps.contains_key("serde"));
+    assert!(deps.contains_key("rand"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+/// Names of the dependencies `config` would hoist in `root`, sorted
+fn hoisted_names(config: &Config, root: &Path) -> Vec<String> {
+    cargo_easy_dep::list_workspace(config, root)
+        .unwrap()
+        .into_iter()
+        .map(|info| info.dep.name)
+        .collect()
+}
+
+#[test]
+fn threshold_percent_rounds_up_and_combines_with_min_occurrences() {
+    let percent = |threshold_percent, min_occurrences| Config {
+        threshold_percent: Some(threshold_percent),
+        min_occurrences,
+        ..Config::default()
+    };
+    // Half of 3 members is 1.5, so 2 of them are needed
+    assert_eq!(percent(50, 1).required_occurrences(3), 2);
+    assert_eq!(percent(34, 1).required_occurrences(3), 2);
+    assert_eq!(percent(67, 1).required_occurrences(3), 3);
+    assert_eq!(percent(1, 1).required_occurrences(3), 1);
+    assert_eq!(percent(100, 1).required_occurrences(3), 3);
+    // The stricter of the two applies
+    assert_eq!(percent(50, 3).required_occurrences(3), 3);
+    assert_eq!(percent(100, 2).required_occurrences(3), 3);
+
+    // Of 4 members, serde is used by 4, itoa by 3, rand by 2 and log by 1
+    let root = fixture("usage-levels");
+    let hoisted = |threshold_percent, min_occurrences| {
+        hoisted_names(&percent(threshold_percent, min_occurrences), &root)
+    };
+    assert_eq!(hoisted(50, 1), ["itoa", "rand", "serde"]);
+    assert_eq!(hoisted(51, 1), ["itoa", "serde"]);
+    assert_eq!(hoisted(75, 1), ["itoa", "serde"]);
+    assert_eq!(hoisted(76, 1), ["serde"]);
+    assert_eq!(hoisted(25, 1), ["itoa", "log", "rand", "serde"]);
+    assert_eq!(hoisted(25, 2), ["itoa", "rand", "serde"]);
+    assert_eq!(hoisted(50, 4), ["serde"]);
+    fs::remove_dir_all(root).unwrap();
+
+    // Out of range percentages and --all are rejected
+    for args in [
+        &["--threshold-percent", "0"][..],
+        &["--threshold-percent", "101"],
+        &["--threshold-percent", "50", "--all"],
+    ] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+            .arg("easy-dep")
+            .args(args)
+            .args(["--dry-run", "--workspace-root", "."])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2), "{args:?} was accepted");
+    }
+}
+
+#[test]
+fn dep_only_hoists_the_named_dependencies() {
+    // log is only used by a, but requested by name
+    let root = fixture("usage-levels");
+    let config = Config {
+        only_deps: vec!["itoa".to_string(), "log".to_string()],
+        ..config(&root)
+    };
+    assert_eq!(hoisted_names(&config, &root), ["itoa", "log"]);
+    run_workspace(&config, &root).unwrap();
+
+    let a = manifest(&root.join("a/Cargo.toml"));
+    assert_eq!(a["dependencies"]["itoa"]["workspace"].as_bool(), Some(true));
+    assert_eq!(a["dependencies"]["log"]["workspace"].as_bool(), Some(true));
+    assert_eq!(a["dependencies"]["serde"].as_str(), Some("1"));
+    assert_eq!(a["dependencies"]["rand"].as_str(), Some("0.8"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn skip_pre_1_leaves_pre_stable_dependencies() {
+    // rand is required as 0.8 by a and b
+    let root = fixture("usage-levels");
+    assert_eq!(
+        hoisted_names(&config(&root), &root),
+        ["itoa", "rand", "serde"]
+    );
+    let config = Config {
+        skip_pre_1: true,
+        ..config(&root)
+    };
+    assert_eq!(hoisted_names(&config, &root), ["itoa", "serde"]);
+    run_workspace(&config, &root).unwrap();
+
+    for member in ["a", "b"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        assert_eq!(member["dependencies"]["rand"].as_str(), Some("0.8"));
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn keep_version_leaves_the_named_dependencies() {
+    let root = fixture("usage-levels");
+    let config = Config {
+        keep_version: vec!["serde".to_string()],
+        ..config(&root)
+    };
+    assert_eq!(hoisted_names(&config, &root), ["itoa", "rand"]);
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert!(
+        workspace["workspace"]["dependencies"]
+            .get("serde")
+            .is_none()
+    );
+    for member in ["a", "b", "c", "d"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        assert_eq!(member["dependencies"]["serde"].as_str(), Some("1"));
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn command_line_overrides_conflicting_config_file_options() {
+    let root = fixture("usage-levels");
+    let config_path = root.join("easy-dep.toml");
+    fs::write(&config_path, "inline-tables = true\nmin-occurrences = 4\n").unwrap();
+
+    // --section-tables conflicts with the file's inline-tables, the command line wins
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+        .args(["easy-dep", "--section-tables", "--no-banner", "--config"])
+        .arg(&config_path)
+        .arg("--workspace-root")
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The file's min-occurrences still applies, only serde is used by all 4 members
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(
+        member.contains("[dependencies.serde]\nworkspace = true\n"),
+        "not in section form:\n{member}"
+    );
+    assert!(
+        member.contains("itoa = \"1\"\n"),
+        "itoa was hoisted:\n{member}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn metadata_cache_is_keyed_and_invalidated_by_new_members() {
+    // a uses serde and itoa, b only serde, under the `crates/*` glob
+    let root = fixture("excluded-member");
+    let cache_path = root.join("metadata.json");
+    let config = Config {
+        metadata_cache: Some(cache_path.clone()),
+        ..config(&root)
+    };
+    assert_eq!(hoisted_names(&config, &root), ["serde"]);
+    let cache = fs::read(&cache_path).unwrap();
+    let written_at = fs::metadata(&cache_path).unwrap().modified().unwrap();
+
+    // Reused as long as nothing changed
+    assert_eq!(hoisted_names(&config, &root), ["serde"]);
+    assert_eq!(fs::read(&cache_path).unwrap(), cache);
+    assert_eq!(
+        fs::metadata(&cache_path).unwrap().modified().unwrap(),
+        written_at
+    );
+
+    // A new member matching the glob, no cached manifest changed
+    let member = root.join("crates/c");
+    fs::create_dir_all(member.join("src")).unwrap();
+    fs::write(member.join("src/lib.rs"), "").unwrap();
+    fs::write(
+        member.join("Cargo.toml"),
+        "[package]\nname = \"c\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\nitoa = \"1\"\n",
+    )
+    .unwrap();
+    assert_eq!(hoisted_names(&config, &root), ["itoa", "serde"]);
+
+    // Reached from another path, e.g. a member directory
+    let from_member = root.join("crates/a");
+    hoisted_names(&config, &from_member);
+    let cache: serde_json::Value = serde_json::from_slice(&fs::read(&cache_path).unwrap()).unwrap();
+    assert_eq!(
+        cache["key"].as_str().map(PathBuf::from),
+        Some(fs::canonicalize(&from_member).unwrap())
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn duplicates_are_only_warned_about_within_a_target() {
+    // a declares serde again for unix, b twice in [dependencies] under a rename
+    let root = fixture("duplicates");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+        .args([
+            "easy-dep",
+            "--warn-duplicates",
+            "--dry-run",
+            "--workspace-root",
+        ])
+        .arg(&root)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("member 'b' declares 'serde' 2 times in [dependencies]"),
+        "missing warning:\n{stderr}"
+    );
+    assert!(
+        !stderr.contains("member 'a'"),
+        "target-specific declaration warned about:\n{stderr}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn threshold_overrides_take_precedence() {
+    // Of 4 members, serde is used by 4, itoa by 3, rand by 2 and log by 1, and the root requires
+    // 1 occurrence of log and 5 of serde
+    let root = fixture("threshold-overrides");
+    let hoisted = |config: Config| hoisted_names(&config, &root);
+    assert_eq!(hoisted(config(&root)), ["itoa", "log", "rand"]);
+    assert_eq!(
+        hoisted(Config {
+            min_occurrences: 4,
+            ..config(&root)
+        }),
+        ["log"]
+    );
+    assert_eq!(
+        hoisted(Config {
+            threshold_percent: Some(75),
+            ..config(&root)
+        }),
+        ["itoa", "log"]
+    );
+    // --all hoists everything, overrides included
+    assert_eq!(
+        hoisted(Config {
+            all: true,
+            ..config(&root)
+        }),
+        ["itoa", "log", "rand", "serde"]
+    );
+
+    let root_manifest = root.join("Cargo.toml");
+    let content = fs::read_to_string(&root_manifest).unwrap();
+    fs::write(&root_manifest, content.replace("serde = 5", "serde = 0")).unwrap();
+    let err = cargo_easy_dep::list_workspace(&config(&root), &root).unwrap_err();
+    assert!(
+        matches!(&err, AppError::InvalidOptions(message) if message.contains("thresholds.serde")),
+        "unexpected error: {err}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn require_kinds_only_hoists_dependencies_used_as_every_kind() {
+    // serde is a normal dependency of a and a dev-dependency of b, itoa is a normal one of both
+    let root = fixture("kinds");
+    let config = Config {
+        require_kinds: vec![DependencyKind::Normal, DependencyKind::Development],
+        min_occurrences: 1,
+        ..config(&root)
+    };
+    assert_eq!(hoisted_names(&config, &root), ["serde"]);
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert_eq!(
+        deps.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["serde"]
+    );
+    let member = manifest(&root.join("b/Cargo.toml"));
+    assert_eq!(
+        member["dev-dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+    assert_eq!(member["dependencies"]["itoa"].as_str(), Some("1"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn only_kinds_restrict_hoisting_to_their_tables() {
+    let root = fixture("kinds");
+    let only = |kinds: &[DependencyKind]| Config {
+        only_kinds: kinds.to_vec(),
+        ..config(&root)
+    };
+    // serde is used once as each kind, so it's never common to a single one
+    assert_eq!(
+        hoisted_names(&only(&[DependencyKind::Normal]), &root),
+        ["itoa"]
+    );
+    assert_eq!(
+        hoisted_names(&only(&[DependencyKind::Development]), &root),
+        ["proptest"]
+    );
+    assert_eq!(
+        hoisted_names(&only(&[DependencyKind::Build]), &root),
+        ["cc"]
+    );
+    assert_eq!(
+        hoisted_names(
+            &only(&[DependencyKind::Development, DependencyKind::Build]),
+            &root
+        ),
+        ["cc", "proptest"]
+    );
+
+    run_workspace(&only(&[DependencyKind::Build]), &root).unwrap();
+    for member in ["a", "b"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        assert_eq!(
+            member["build-dependencies"]["cc"]["workspace"].as_bool(),
+            Some(true)
+        );
+        assert_eq!(member["dependencies"]["itoa"].as_str(), Some("1"));
+        assert_eq!(member["dev-dependencies"]["proptest"].as_str(), Some("1"));
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn per_kind_excludes_only_skip_that_kind() {
+    let root = fixture("kinds");
+    let config = Config {
+        exclude: vec![
+            (DependencyKind::Build, "cc".to_string()),
+            (DependencyKind::Development, "serde".to_string()),
+        ],
+        min_occurrences: 1,
+        ..config(&root)
+    };
+    // serde is still hoisted for its normal use in a
+    assert_eq!(hoisted_names(&config, &root), ["itoa", "proptest", "serde"]);
+    run_workspace(&config, &root).unwrap();
+
+    let a = manifest(&root.join("a/Cargo.toml"));
+    assert_eq!(
+        a["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+    assert_eq!(a["build-dependencies"]["cc"].as_str(), Some("1"));
+    let b = manifest(&root.join("b/Cargo.toml"));
+    assert_eq!(b["dev-dependencies"]["serde"].as_str(), Some("1"));
+    assert_eq!(
+        b["dev-dependencies"]["proptest"]["workspace"].as_bool(),
+        Some(true)
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn on_conflict_policies() {
+    // Aborts without writing anything
+    let root = fixture("version-conflict");
+    let before = snapshot(&root);
+    let error = Config {
+        on_conflict: ConflictPolicy::Error,
+        ..config(&root)
+    };
+    let err = run_workspace(&error, &root).unwrap_err();
+    assert!(
+        matches!(&err, AppError::VersionConflict(name, requirements)
+            if name == "serde" && requirements == "^1.0.100, ^1.0.200"),
+        "unexpected error: {err}"
+    );
+    assert_eq!(before, snapshot(&root));
+    fs::remove_dir_all(root).unwrap();
+
+    // Skips the dependency, leaving both members untouched
+    let root = fixture("version-conflict");
+    let before = snapshot(&root);
+    let warn = Config {
+        on_conflict: ConflictPolicy::Warn,
+        ..config(&root)
+    };
+    let summary = run_workspace(&warn, &root).unwrap();
+    assert_eq!(summary.common_deps, 0);
+    assert_eq!(before, snapshot(&root));
+    fs::remove_dir_all(root).unwrap();
+
+    // Hoists the requirement of the first member
+    let root = fixture("version-conflict");
+    let first = Config {
+        on_conflict: ConflictPolicy::First,
+        ..config(&root)
+    };
+    let summary = run_workspace(&first, &root).unwrap();
+    assert_eq!(summary.common_deps, 1);
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["workspace"]["dependencies"]["serde"]["version"].as_str(),
+        Some("^1.0.200")
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn same_crate_from_two_registries_is_an_error() {
+    let root = fixture("registry-conflict");
+    let before = snapshot(&root);
+
+    let err = run_workspace(&config(&root), &root).unwrap_err();
+    assert!(
+        matches!(&err, AppError::RegistryConflict(name, registries)
+            if name == "itoa" && registries == "crates.io, private"),
+        "unexpected error: {err}"
+    );
+    for ((path, before), (_, after)) in before.iter().zip(snapshot(&root)) {
+        assert_eq!(*before, after, "{} was rewritten", path.display());
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn root_section_entry_is_not_duplicated() {
+    let root = fixture("root-section-entry");
+    run_workspace(&config(&root), &root).unwrap();
+
+    let content = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(
+        content.matches("serde").count(),
+        1,
+        "duplicated:\n{content}"
+    );
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let serde = &workspace["workspace"]["dependencies"]["serde"];
+    assert!(serde.is_table());
+    assert_eq!(serde["version"].as_str(), Some("1.0"));
+    let member = manifest(&root.join("a/Cargo.toml"));
+    assert_eq!(
+        member["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn root_diff_writes_nothing() {
+    let root = fixture("section-form");
+    let root_before = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    let member_before = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    let config = Config {
+        root_diff: true,
+        ..config(&root)
+    };
+    let summary = run_workspace(&config, &root).unwrap();
+    assert!(summary.root_modified);
+    assert_eq!(summary.members_updated, 2);
+
+    let root_after = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(root_before, root_after);
+    let member_after = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert_eq!(member_before, member_after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn dedupe_features_only_moves_uniform_feature_lists() {
+    let root = fixture("dedupe-features");
+    let config = Config {
+        features_strategy: FeaturesStrategy::Uniform,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = &workspace["workspace"]["dependencies"];
+    let serde_features = deps["serde"]["features"].as_array().unwrap();
+    assert_eq!(
+        serde_features
+            .iter()
+            .map(|f| f.as_str().unwrap())
+            .collect::<Vec<_>>(),
+        ["derive"]
+    );
+    assert!(deps["rand"].get("features").is_none());
+    for member in ["a", "b"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        assert!(member["dependencies"]["serde"].get("features").is_none());
+        assert!(member["dependencies"]["rand"].get("features").is_some());
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn inherited_and_raw_declarations_are_counted_once() {
+    let root = fixture("mixed-inheritance");
+    let common_deps = cargo_easy_dep::list_workspace(&config(&root), &root).unwrap();
+    assert_eq!(common_deps.len(), 1);
+    assert_eq!(common_deps[0].occurrences, 3);
+    // Attributed to the existing workspace entry rather than the first raw declaration
+    assert_eq!(common_deps[0].dep.req.to_string(), "^1.0.100");
+
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    assert!(!summary.root_modified);
+    assert_eq!(summary.members_updated, 2);
+
+    let content = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(
+        content.matches("serde").count(),
+        1,
+        "duplicated:\n{content}"
+    );
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["workspace"]["dependencies"]["serde"]["version"].as_str(),
+        Some("1.0.100")
+    );
+    for member in ["a", "b", "c"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        let serde = &member["dependencies"]["serde"];
+        assert_eq!(serde["workspace"].as_bool(), Some(true));
+        assert!(serde.get("version").is_none());
+    }
+
+    // Every member now inherits it, nothing is left to change
+    let config = Config {
+        locked: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn group_by_kind_writes_headers_once() {
+    let root = fixture("group-by-kind");
+    let config = Config {
+        group_by_kind: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert!(
+        workspace.ends_with(
+            "[workspace.dependencies]\n# normal\nserde = { default-features = false, version = \"^1\" }\n\n# dev\nproptest = { default-features = false, version = \"^1\" }\n\n# unused\nitoa = \"1\"\n"
+        ),
+        "entries were not grouped:\n{workspace}"
+    );
+
+    // A second run keeps the headers as they are
+    let config = Config {
+        locked: true,
+        ..config
+    };
+    run_workspace(&config, &root).unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn parse_errors_are_located() {
+    let root = fixture("no-metadata");
+    fs::write(
+        root.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\nmembers = []\n",
+    )
+    .unwrap();
+    let config = Config {
+        no_metadata: true,
+        ..config(&root)
+    };
+
+    let err = run_workspace(&config, &root).unwrap_err();
+    assert!(
+        matches!(err, AppError::TomlParse(_, _, Some((3, 1)))),
+        "unexpected error: {err}"
+    );
+    assert!(
+        err.to_string().contains("Cargo.toml:3:1': duplicate key"),
+        "unexpected message: {err}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn apply_to_patch_moves_shared_patches_to_the_root() {
+    let root = fixture("shared-patch");
+    let config = Config {
+        apply_to_patch: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["patch"]["crates-io"]["serde"]["git"].as_str(),
+        Some("https://github.com/serde-rs/serde")
+    );
+    for member in ["a", "b"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        assert!(!member.contains_key("patch"), "patch was kept:\n{member}");
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn patch_paths_are_rebased_on_the_root() {
+    let root = fixture("path-patch");
+    let config = Config {
+        apply_to_patch: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    // Both members point to the same crate, from different directories
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let path = workspace["patch"]["crates-io"]["itoa"]["path"]
+        .as_str()
+        .unwrap();
+    assert_eq!(path, "vendor/itoa");
+    assert!(root.join(path).join("Cargo.toml").is_file());
+    for member in ["crates/a", "crates/tools/b"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        assert!(!member.contains_key("patch"), "patch was kept:\n{member}");
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn differently_spelled_names_are_merged() {
+    let root = fixture("name-spellings");
+    run_workspace(&config(&root), &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert!(deps["serde_json"].get("package").is_none());
+    // The other spelling resolves to the same crate
+    assert_eq!(deps["serde-json"]["package"].as_str(), Some("serde_json"));
+    assert_eq!(
+        deps["serde-json"]["version"].as_str(),
+        deps["serde_json"]["version"].as_str()
+    );
+
+    let member = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    assert!(
+        member.contains(
+            "# json support\nserde-json = { workspace = true, default-features = true }\nitoa = \"1\"\n"
+        ),
+        "member spelling was not kept:\n{member}"
+    );
+    let member = manifest(&root.join("a/Cargo.toml"));
+    assert_eq!(
+        member["dependencies"]["serde_json"]["workspace"].as_bool(),
+        Some(true)
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn workspace_siblings_survive_byte_for_byte() {
+    let root = fixture("rich-workspace");
+    let original = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    run_workspace(&config(&root), &root).unwrap();
+
+    let expected = original.replace(
+        "[profile.release]\n",
+        "[workspace.dependencies]\nserde = { default-features = false, version = \"^1\" }\n\n[profile.release]\n",
+    );
+    let updated = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(updated, expected);
+
+    // Rewriting the existing entries leaves the siblings alone as well
+    let config = Config {
+        prune: true,
+        group_by_kind: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+    let regrouped = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(
+        regrouped,
+        expected.replace(
+            "[workspace.dependencies]\n",
+            "[workspace.dependencies]\n# normal\n"
+        )
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn all_hoists_dependencies_used_once() {
+    let root = fixture("excluded-member");
+    let config = Config {
+        all: true,
+        ..config(&root)
+    };
+    let summary = run_workspace(&config, &root).unwrap();
+    assert_eq!(summary.common_deps, 2);
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert!(deps.contains_key("serde"));
+    assert!(deps.contains_key("itoa"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+/// Every file below `dir` along with its content
+fn snapshot(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(snapshot(&path));
+        } else {
+            files.push((path.clone(), fs::read(&path).unwrap()));
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn rerun_after_adding_an_inheriting_member_is_a_no_op() {
+    let root = fixture("generated");
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    assert!(summary.root_modified);
+    assert_eq!(summary.members_updated, 2);
+
+    // A new member written against the hoisted workspace entry
+    fs::create_dir_all(root.join("crates/c/src")).unwrap();
+    fs::write(root.join("crates/c/src/lib.rs"), "").unwrap();
+    fs::write(
+        root.join("crates/c/Cargo.toml"),
+        "[package]\nname = \"c\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\nserde = { workspace = true }\n",
+    )
+    .unwrap();
+    let before = snapshot(&root);
+
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    assert_eq!(summary.members, 3);
+    assert!(!summary.root_modified, "second run rewrote the root");
+    assert_eq!(summary.members_updated, 0);
+    assert_eq!(summary.stats.bytes_written, 0);
+    for ((path, before), (_, after)) in before.iter().zip(snapshot(&root)) {
+        assert_eq!(*before, after, "second run rewrote {}", path.display());
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn second_run_changes_nothing() {
+    let fixtures = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap());
+    for name in fixtures {
+        for options in [
+            Config::default(),
+            Config {
+                features_strategy: FeaturesStrategy::Intersection,
+                hoist_path_versions: true,
+                group_by_kind: true,
+                apply_to_patch: true,
+                table_style: TableStyle::Section,
+                keep_sorted: true,
+                ..Config::default()
+            },
+            Config {
+                features_strategy: FeaturesStrategy::Uniform,
+                normalize_versions: true,
+                prune: true,
+                prune_empty_tables: true,
+                table_style: TableStyle::Inline,
+                keep_sorted: true,
+                ..Config::default()
+            },
+            Config {
+                features_strategy: FeaturesStrategy::Union,
+                root_table_style: Some(TableStyle::Section),
+                ..Config::default()
+            },
+        ] {
+            let root = fixture(&name);
+            let config = Config {
+                no_metadata: name == "no-metadata",
+                ..Config {
+                    workspace_roots: vec![root.clone()],
+                    quiet: true,
+                    ..options
+                }
+            };
+            // Some fixtures are only meant to fail
+            if run_workspace(&config, &root).is_err() {
+                fs::remove_dir_all(root).unwrap();
+                continue;
+            }
+            let first = snapshot(&root);
+
+            let summary = run_workspace(&config, &root).unwrap();
+            assert!(
+                !summary.root_modified
+                    && summary.members_updated == 0
+                    && summary.stats.bytes_written == 0,
+                "second run of {name} changed manifests: {summary:?}"
+            );
+            for ((path, before), (_, after)) in first.iter().zip(snapshot(&root)) {
+                assert_eq!(
+                    String::from_utf8_lossy(before),
+                    String::from_utf8_lossy(&after),
+                    "second run of {name} rewrote {}",
+                    path.display()
+                );
+            }
+
+            fs::remove_dir_all(root).unwrap();
+        }
+    }
+}
+
+#[test]
+fn lock_changes_report_members_resolving_elsewhere() {
+    let root = fixture("lock-changes");
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .current_dir(&root)
+        .no_deps()
+        .exec()
+        .unwrap();
+    let common_deps = find_common_dependencies(&metadata, &config(&root)).unwrap();
+
+    let changes = lock_changes(&metadata, &common_deps).unwrap();
+    let [change] = changes.as_slice() else {
+        panic!("expected a single change: {changes:?}");
+    };
+    assert_eq!(change.name, "serde");
+    assert_eq!(change.member, "b");
+    assert_eq!(change.locked.to_string(), "1.0.150");
+    assert_eq!(change.requirement.to_string(), "^1.0.200");
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn concurrent_run_waits_for_the_lock() {
+    let root = fixture("section-form");
+    let lock_path = cargo_easy_dep::workspace_lock_path(&fs::canonicalize(&root).unwrap());
+    let lock = fs::File::create(&lock_path).unwrap();
+    lock.lock().unwrap();
+
+    let started = std::time::Instant::now();
+    let holder = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        drop(lock);
+    });
+    run_workspace(&config(&root), &root).unwrap();
+    assert!(started.elapsed() >= std::time::Duration::from_millis(300));
+    holder.join().unwrap();
+    fs::remove_file(lock_path).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert!(
+        workspace["workspace"]["dependencies"]
+            .get("serde")
+            .is_some()
+    );
+    // Locking leaves nothing in the source tree
+    assert!(!root.join("target").exists());
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/section-form");
+    assert_eq!(
+        fs::read_dir(&root).unwrap().count(),
+        fs::read_dir(source).unwrap().count()
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn output_preview_takes_no_lock() {
+    let root = fixture("section-form");
+    let lock_path = cargo_easy_dep::workspace_lock_path(&fs::canonicalize(&root).unwrap());
+    let lock = fs::File::create(&lock_path).unwrap();
+    lock.lock().unwrap();
+
+    // Would wait forever on the lock held above
+    let config = Config {
+        output: Some(PathBuf::from("-")),
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+    drop(lock);
+    fs::remove_file(lock_path).unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn common_dependencies_keep_each_member_requirement() {
+    let root = fixture("mixed-inheritance");
+    let common_deps = cargo_easy_dep::list_workspace(&config(&root), &root).unwrap();
+    let serde = common_deps
+        .iter()
+        .find(|info| info.dep.name == "serde")
+        .unwrap();
+    let mut requirements: Vec<(&str, String)> = serde
+        .requirements
+        .iter()
+        .map(|(member, req)| (member.as_str(), req.to_string()))
+        .collect();
+    requirements.sort();
+    assert_eq!(requirements.len(), serde.occurrences);
+    assert_eq!(requirements[1], ("b", "^1".to_string()));
+    assert_eq!(requirements[2], ("c", "^1.0".to_string()));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn table_style_rewrites_entries_in_the_requested_form() {
+    let root = fixture("section-form");
+    let sections = Config {
+        table_style: TableStyle::Section,
+        ..config(&root)
+    };
+    run_workspace(&sections, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert!(workspace["workspace"]["dependencies"]["serde"].is_table());
+    let member = manifest(&root.join("b/Cargo.toml"));
+    assert!(member["dependencies"]["serde"].is_table());
+    assert_eq!(
+        member["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+
+    fs::remove_dir_all(root).unwrap();
+
+    let root = fixture("section-form");
+    let inline = Config {
+        table_style: TableStyle::Inline,
+        ..config(&root)
+    };
+    run_workspace(&inline, &root).unwrap();
+
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(member.contains("serde = { features = [\"derive\"], workspace = true"));
+    assert!(!member.contains("[dependencies.serde]"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn missing_root_manifest_is_reported() {
+    let root = std::env::temp_dir().join(format!("cargo-easy-dep-missing-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+
+    let err = run_workspace(&config(&root), &root).unwrap_err();
+    assert!(
+        matches!(&err, AppError::ManifestNotFound(path) if *path == root.join("Cargo.toml")),
+        "unexpected error: {err}"
+    );
+    assert!(
+        err.to_string().contains("--workspace-root"),
+        "unexpected message: {err}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn root_manifest_not_utf8_is_reported() {
+    let root = fixture("section-form");
+    let mut content = fs::read(root.join("Cargo.toml")).unwrap();
+    content.extend_from_slice(b"# \xff\n");
+    fs::write(root.join("Cargo.toml"), content).unwrap();
+
+    for no_metadata in [false, true] {
+        let config = Config {
+            no_metadata,
+            ..config(&root)
+        };
+        let err = run_workspace(&config, &root).unwrap_err();
+        assert!(
+            matches!(&err, AppError::ManifestNotUtf8(path) if *path == root.join("Cargo.toml")),
+            "unexpected error: {err}"
+        );
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn keep_sorted_sorts_workspace_dependencies() {
+    let root = fixture("unsorted");
+    let check = Config {
+        keep_sorted: true,
+        locked: true,
+        ..config(&root)
+    };
+    let err = run_workspace(&check, &root).unwrap_err();
+    assert!(
+        matches!(err, AppError::Unsorted(_)),
+        "unexpected error: {err}"
+    );
+
+    let config = Config {
+        keep_sorted: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let names: Vec<&str> = workspace["workspace"]["dependencies"]
+        .as_table()
+        .unwrap()
+        .iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, ["anyhow", "serde", "toml"]);
+
+    // Sorted entries pass the check
+    run_workspace(&check, &root).unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn inheritance_the_root_cannot_satisfy_is_left_untouched() {
+    let root = fixture("nested-inheritance");
+    let inner = root.join("inner");
+    let config = Config {
+        no_metadata: true,
+        all: true,
+        ..config(&inner)
+    };
+    run_workspace(&config, &inner).unwrap();
+
+    let workspace = manifest(&inner.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert!(deps.contains_key("serde"));
+    assert!(!deps.contains_key("anyhow"));
+    for member in ["x", "y"] {
+        let member = fs::read_to_string(inner.join(member).join("Cargo.toml")).unwrap();
+        assert!(member.contains("anyhow = { workspace = true }\n"));
+    }
+    let outer = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(
+        outer,
+        fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/nested-inheritance/Cargo.toml")
+        )
+        .unwrap()
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn excluded_sources_are_left_unhoisted() {
+    let root = fixture("sources");
+    let private = Config {
+        exclude_git: true,
+        exclude_registries: vec!["private".to_string()],
+        ..config(&root)
+    };
+    run_workspace(&private, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert_eq!(
+        deps.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["serde"]
+    );
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(member.contains("anyhow = { git = \"https://github.com/dtolnay/anyhow\" }\n"));
+    assert!(member.contains("itoa = { version = \"1\", registry = \"private\" }\n"));
+
+    fs::remove_dir_all(root).unwrap();
+
+    // Only the private registry's dependencies are left once crates.io is excluded
+    let root = fixture("sources");
+    let crates_io = Config {
+        exclude_git: true,
+        exclude_registries: vec!["crates-io".to_string()],
+        ..config(&root)
+    };
+    let common_deps = cargo_easy_dep::list_workspace(&crates_io, &root).unwrap();
+    assert_eq!(
+        common_deps
+            .iter()
+            .map(|info| info.dep.name.as_str())
+            .collect::<Vec<_>>(),
+        ["itoa"]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn trace_file_records_reads_and_writes() {
+    let root = fixture("section-form");
+    let trace_path = root.join("trace.log");
+    let config = Config {
+        trace_file: Some(trace_path.clone()),
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    for manifest in ["Cargo.toml", "a/Cargo.toml", "b/Cargo.toml"] {
+        let path = root.join(manifest);
+        assert!(
+            trace.contains(&format!(" read {}\n", path.display())),
+            "missing read of {manifest}:\n{trace}"
+        );
+        assert!(
+            trace.contains(&format!(" write {} modified\n", path.display())),
+            "missing write of {manifest}:\n{trace}"
+        );
+    }
+
+    // A second run only reads, and appends to the same trace
+    run_workspace(&config, &root).unwrap();
+    let second = fs::read_to_string(&trace_path).unwrap();
+    let appended = second.strip_prefix(trace.as_str()).unwrap();
+    assert!(!appended.is_empty());
+    assert!(
+        !appended.contains(" write "),
+        "unexpected writes:\n{appended}"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn differing_feature_lists_stay_on_the_members() {
+    for options in [
+        Config::default(),
+        Config {
+            features_strategy: FeaturesStrategy::Intersection,
+            ..Config::default()
+        },
+        Config {
+            features_strategy: FeaturesStrategy::Uniform,
+            ..Config::default()
+        },
+    ] {
+        let root = fixture("feature-sets");
+        let config = Config {
+            workspace_roots: vec![root.clone()],
+            quiet: true,
+            ..options
+        };
+        run_workspace(&config, &root).unwrap();
+
+        let workspace = manifest(&root.join("Cargo.toml"));
+        let uuid = &workspace["workspace"]["dependencies"]["uuid"];
+        assert_eq!(uuid["version"].as_str(), Some("^1"));
+        assert!(uuid.get("features").is_none());
+        for (member, feature) in [("a", "v4"), ("b", "serde")] {
+            let member = manifest(&root.join(member).join("Cargo.toml"));
+            let uuid = &member["dependencies"]["uuid"];
+            assert_eq!(uuid["workspace"].as_bool(), Some(true));
+            assert!(uuid.get("version").is_none());
+            let features: Vec<&str> = uuid["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f.as_str().unwrap())
+                .collect();
+            assert_eq!(features, [feature]);
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
+
+#[test]
+fn prune_empty_tables_removes_bare_dependency_headers() {
+    let root = fixture("empty-tables");
+    let config = Config {
+        prune_empty_tables: true,
+        ..config(&root)
+    };
+    run_workspace(&config, &root).unwrap();
+
+    let member = manifest(&root.join("a/Cargo.toml"));
+    assert!(member.get("dev-dependencies").is_none());
+    assert!(member.get("target").is_none());
+    assert_eq!(
+        member["dependencies"]["serde"]["workspace"].as_bool(),
+        Some(true)
+    );
+    assert!(member.get("features").is_some());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn too_old_cargo_is_reported() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = fixture("section-form");
+    let cargo = root.join("old-cargo");
+    fs::write(
+        &cargo,
+        "#!/bin/sh\necho 'cargo 1.60.0 (d1ecc3d 2022-02-22)'\n",
+    )
+    .unwrap();
+    fs::set_permissions(&cargo, fs::Permissions::from_mode(0o755)).unwrap();
+    let member_before = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+
+    // The version is checked in a separate process, `CARGO` would leak into the other tests
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+        .args(["easy-dep", "--workspace-root"])
+        .arg(&root)
+        .env("CARGO", &cargo)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("cargo 1.60.0 is too old") && stderr.contains("1.64"),
+        "unexpected error:\n{stderr}"
+    );
+    let member_after = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert_eq!(member_before, member_after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn running_from_a_member_hoists_into_the_enclosing_root() {
+    let root = fixture("section-form");
+    let member_dir = root.join("a");
+    let member_before = fs::read_to_string(member_dir.join("Cargo.toml")).unwrap();
+    let sub_dir = member_dir.join("src");
+
+    run(&config(&sub_dir)).unwrap();
+
+    let root_manifest = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert!(
+        root_manifest.contains("[workspace.dependencies]\nserde = "),
+        "the workspace root was not updated:\n{root_manifest}"
+    );
+    let member_after = fs::read_to_string(member_dir.join("Cargo.toml")).unwrap();
+    assert_ne!(member_before, member_after);
+    assert!(
+        !member_after.contains("[workspace"),
+        "the member was treated as a workspace:\n{member_after}"
+    );
+    assert!(!sub_dir.join("Cargo.toml").exists());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn passing_locked_run_reports_manifests_already_hoisted() {
+    let root = fixture("optional");
+    run_workspace(&config(&root), &root).unwrap();
+    let before = snapshot(&root);
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+        .args(["easy-dep", "--locked", "--workspace-root"])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("already use workspace dependencies") && !stdout.contains("Successfully"),
+        "unexpected banner:\n{stdout}"
+    );
+    assert_eq!(before, snapshot(&root));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn explain_prints_decisions_as_json() {
+    let root = fixture("sources");
+
+    // Decisions are printed to stdout, only observable from the binary
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+        .args([
+            "easy-dep",
+            "--explain",
+            "--format",
+            "json",
+            "--exclude-git",
+            "--workspace-root",
+        ])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // Nothing but the decisions on stdout, even without --no-banner
+    let explanation: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let decisions = explanation["decisions"].as_array().unwrap();
+    assert_eq!(
+        decisions
+            .iter()
+            .map(|decision| (
+                decision["name"].as_str().unwrap(),
+                decision["decision"].as_str().unwrap(),
+                decision["reason"].as_str().unwrap(),
+            ))
+            .collect::<Vec<_>>(),
+        [
+            ("anyhow", "skipped", "excluded-source"),
+            ("itoa", "hoisted", "shared"),
+            ("serde", "hoisted", "shared"),
+        ]
+    );
+    assert_eq!(decisions[0]["members"], serde_json::json!(["a", "b"]));
+    assert_eq!(decisions[1]["occurrences"], 2);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn explain_decisions_are_returned_to_the_caller() {
+    let root = fixture("sources");
+
+    let summary = cargo_easy_dep::run(&Config {
+        explain: true,
+        exclude_git: true,
+        dry_run: true,
+        ..config(&root)
+    })
+    .unwrap();
+    assert_eq!(summary.explanations.len(), 1);
+    let decisions = &summary.explanations[0].decisions;
+    assert_eq!(
+        decisions
+            .iter()
+            .map(|decision| (decision.name.as_str(), decision.decision))
+            .collect::<Vec<_>>(),
+        [
+            ("anyhow", "skipped"),
+            ("itoa", "hoisted"),
+            ("serde", "hoisted")
+        ]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn non_string_version_is_reported() {
+    let root = fixture("non-string-version");
+    let member_before = fs::read_to_string(root.join("c/Cargo.toml")).unwrap();
+    let discovered = Config {
+        no_metadata: true,
+        ..config(&root)
+    };
+
+    let err = run_workspace(&discovered, &root).unwrap_err();
+    let AppError::MemberFailures(failures) = &err else {
+        panic!("unexpected error: {err}");
+    };
+    assert!(
+        matches!(
+            failures.as_slice(),
+            [AppError::DependencyUpdate(name, table, msg, path)]
+                if name == "serde"
+                    && table == "dependencies"
+                    && msg.contains("'version' is integer")
+                    && path.ends_with("c/Cargo.toml")
+        ),
+        "unexpected error: {err}"
+    );
+    let member_after = fs::read_to_string(root.join("c/Cargo.toml")).unwrap();
+    assert_eq!(member_before, member_after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn dedupe_report_measures_member_version_declarations() {
+    let root = fixture("section-form");
+    let before = snapshot(&root);
+
+    let savings = dedupe_report(&config(&root), &root).unwrap();
+    // `version = "1"` in the section of a, the bare `"1"` of b
+    assert_eq!(
+        savings
+            .iter()
+            .map(|dep| (dep.name.as_str(), dep.declarations, dep.bytes))
+            .collect::<Vec<_>>(),
+        [("serde", 2, 16)]
+    );
+    assert_eq!(before, snapshot(&root));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_member_manifest_updates_its_target() {
+    for no_follow_symlinks in [false, true] {
+        let root = fixture("section-form");
+        // A vendored manifest shared through a symlink
+        fs::rename(root.join("b/Cargo.toml"), root.join("b.toml")).unwrap();
+        std::os::unix::fs::symlink("../b.toml", root.join("b/Cargo.toml")).unwrap();
+        let target_before = fs::read_to_string(root.join("b.toml")).unwrap();
+        let followed = Config {
+            no_follow_symlinks,
+            ..config(&root)
+        };
+
+        run_workspace(&followed, &root).unwrap();
+
+        assert!(
+            fs::symlink_metadata(root.join("b/Cargo.toml"))
+                .unwrap()
+                .file_type()
+                .is_symlink(),
+            "the symlink was replaced"
+        );
+        let target_after = fs::read_to_string(root.join("b.toml")).unwrap();
+        if no_follow_symlinks {
+            assert_eq!(target_before, target_after);
+        } else {
+            assert!(
+                target_after.contains("serde = { workspace = true"),
+                "the symlink target was not updated:\n{target_after}"
+            );
+        }
+        let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+        assert!(member.contains("workspace = true"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
+
+#[test]
+fn lockfile_version_source_pins_locked_versions() {
+    let root = fixture("lock-changes");
+    let pinned = Config {
+        version_source: VersionSource::Lockfile,
+        ..config(&root)
+    };
+
+    // b still locks 1.0.150, which a's `1.0.200` requirement rules out
+    run_workspace(&pinned, &root).unwrap();
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["workspace"]["dependencies"]["serde"]["version"].as_str(),
+        Some("=1.0.200")
+    );
+
+    fs::remove_dir_all(&root).unwrap();
+
+    // No locked version satisfies both members
+    let root = fixture("lock-changes");
+    let member = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    fs::write(
+        root.join("b/Cargo.toml"),
+        member.replace("serde = \"1.0.200\"", "serde = \"=1.0.150\""),
+    )
+    .unwrap();
+    assert!(matches!(
+        run_workspace(&pinned, &root),
+        Err(AppError::LockedVersionConflict(name, versions))
+            if name == "serde" && versions == "1.0.150, 1.0.200"
+    ));
+    fs::remove_dir_all(&root).unwrap();
+
+    let root = fixture("section-form");
+    let pinned = Config {
+        version_source: VersionSource::Lockfile,
+        ..config(&root)
+    };
+    assert!(matches!(
+        run_workspace(&pinned, &root),
+        Err(AppError::LockfileMissing(_))
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn excluded_optional_dependencies_are_left_unhoisted() {
+    let root = fixture("optional");
+    let required = Config {
+        exclude_optional: true,
+        ..config(&root)
+    };
+    run_workspace(&required, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert_eq!(
+        deps.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["serde"]
+    );
+    let member = fs::read_to_string(root.join("b/Cargo.toml")).unwrap();
+    assert!(
+        member.contains("serde = { version = \"1\", optional = true }")
+            && member.contains("itoa = { version = \"1\", optional = true }"),
+        "optional dependencies were converted:\n{member}"
+    );
+    let member = fs::read_to_string(root.join("c/Cargo.toml")).unwrap();
+    assert!(member.contains("serde = { workspace = true"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn max_fragmentation_rejects_unsettled_dependencies() {
+    let root = fixture("fragmented");
+    let report = report_workspace(&config(&root), &root).unwrap();
+    assert_eq!(
+        report
+            .iter()
+            .map(|dep| (dep.name.as_str(), format!("{:.2}", dep.fragmentation)))
+            .collect::<Vec<_>>(),
+        [("serde", "0.67".to_string()), ("itoa", "0.33".to_string())]
+    );
+
+    let root_before = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    let strict = Config {
+        max_fragmentation: Some(0.5),
+        ..config(&root)
+    };
+    let err = run_workspace(&strict, &root).unwrap_err();
+    assert!(
+        matches!(&err, AppError::Fragmented(deps, _) if deps.len() == 1 && deps[0].0 == "serde"),
+        "unexpected error: {err}"
+    );
+    let root_after = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(root_before, root_after);
+
+    // itoa is required identically, so only serde's ratio matters
+    let lenient = Config {
+        max_fragmentation: Some(0.7),
+        ..config(&root)
+    };
+    run_workspace(&lenient, &root).unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn union_features_strategy_moves_every_feature_to_the_root() {
+    let root = fixture("feature-sets");
+    let union = Config {
+        features_strategy: FeaturesStrategy::Union,
+        ..config(&root)
+    };
+    run_workspace(&union, &root).unwrap();
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let features: Vec<&str> = workspace["workspace"]["dependencies"]["uuid"]["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f.as_str().unwrap())
+        .collect();
+    assert_eq!(features, ["serde", "v4"]);
+    for member in ["a", "b"] {
+        let member = manifest(&root.join(member).join("Cargo.toml"));
+        let uuid = &member["dependencies"]["uuid"];
+        assert_eq!(uuid["workspace"].as_bool(), Some(true));
+        assert!(uuid.get("features").is_none());
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn root_entry_spelled_differently_is_reported_or_renamed() {
+    let root = fixture("root-spelling");
+    let root_before = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+
+    let err = run_workspace(&config(&root), &root).unwrap_err();
+    assert!(
+        matches!(&err, AppError::RootSpelling(mismatches)
+            if mismatches == &[("Serde".to_string(), "serde".to_string())]),
+        "unexpected error: {err}"
+    );
+    let root_after = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert_eq!(root_before, root_after);
+
+    let fixed = Config {
+        fix_root_spelling: true,
+        ..config(&root)
+    };
+    run_workspace(&fixed, &root).unwrap();
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert_eq!(
+        deps.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["serde"]
+    );
+    // The version the workspace settled on is kept
+    assert_eq!(deps["serde"]["version"].as_str(), Some("1"));
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(member.contains("serde = { workspace = true"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn members_from_file_restricts_the_members() {
+    let root = fixture("fragmented");
+    let members_path = root.join("members.txt");
+    fs::write(&members_path, "# computed elsewhere\na\n\nb\nmissing\n").unwrap();
+    let unlisted_before = fs::read_to_string(root.join("c/Cargo.toml")).unwrap();
+
+    let listed = Config {
+        members_from_file: Some(members_path),
+        ..config(&root)
+    };
+    let summary = run_workspace(&listed, &root).unwrap();
+    assert_eq!(summary.members, 2);
+    assert_eq!(summary.members_updated, 2);
+
+    // Only a and b are counted, which agree on serde's version
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["workspace"]["dependencies"]["serde"]["version"].as_str(),
+        Some("^1.0.100")
+    );
+    let unlisted_after = fs::read_to_string(root.join("c/Cargo.toml")).unwrap();
+    assert_eq!(unlisted_before, unlisted_after);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn wildcard_requirements_are_only_hoisted_when_allowed() {
+    let root = fixture("wildcard");
+    let summary = run_workspace(&config(&root), &root).unwrap();
+    assert_eq!(summary.common_deps, 1);
+
+    let workspace = manifest(&root.join("Cargo.toml"));
+    let deps = workspace["workspace"]["dependencies"].as_table().unwrap();
+    assert_eq!(
+        deps.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["serde"]
+    );
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(
+        member.contains("itoa = \"*\""),
+        "wildcard dependency was converted:\n{member}"
+    );
+
+    let allowed = Config {
+        allow_wildcard: true,
+        ..config(&root)
+    };
+    run_workspace(&allowed, &root).unwrap();
+    let workspace = manifest(&root.join("Cargo.toml"));
+    assert_eq!(
+        workspace["workspace"]["dependencies"]["itoa"]["version"].as_str(),
+        Some("*")
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn root_table_style_only_applies_to_root_entries() {
+    let root = fixture("optional");
+    let expanded = Config {
+        table_style: TableStyle::Inline,
+        root_table_style: Some(TableStyle::Section),
+        ..config(&root)
+    };
+    run_workspace(&expanded, &root).unwrap();
+
+    let workspace = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    assert!(
+        workspace.contains("[workspace.dependencies.serde]\ndefault-features = false\n")
+            && !workspace.contains("[workspace.dependencies]\n"),
+        "root entries weren't expanded:\n{workspace}"
+    );
+    let member = manifest(&root.join("c/Cargo.toml"));
+    assert!(member["dependencies"]["serde"].is_inline_table());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn print_workspace_deps_renders_the_root_block_without_writing() {
+    let root = fixture("optional");
+    let before = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    let sorted = Config {
+        keep_sorted: true,
+        ..config(&root)
+    };
+    let snippet = workspace_dependencies_snippet(&sorted, &root).unwrap();
+    assert_eq!(
+        snippet,
+        "[workspace.dependencies]\n\
+         itoa = { default-features = false, version = \"^1\" }\n\
+         serde = { default-features = false, version = \"^1\" }\n"
+    );
+    assert_eq!(fs::read_to_string(root.join("Cargo.toml")).unwrap(), before);
+    let member = fs::read_to_string(root.join("a/Cargo.toml")).unwrap();
+    assert!(member.contains("serde = \"1\""));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+/// Writes a package manifest depending on serde where a build or a tool would leave one
+fn generated_manifest(dir: &Path) -> String {
+    let manifest =
+        "[package]\nname = \"packaged\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\n";
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "").unwrap();
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    manifest.to_string()
+}
+
+#[test]
+fn generated_manifests_are_never_rewritten() {
+    // The target directory follows the environment, which every run sets explicitly
+    let easy_dep = |root: &Path, args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+            .args(["easy-dep", "--quiet"])
+            .args(args)
+            .arg("--workspace-root")
+            .arg(root)
+            .env("CARGO_TARGET_DIR", root.join("crates/out"))
+            .output()
+            .unwrap()
+    };
+
+    let root = fixture("generated");
+    let built = generated_manifest(&root.join("crates/out"));
+    let cached = generated_manifest(&root.join("crates/.cache"));
+    let output = easy_dep(&root, &["--no-metadata"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let member = fs::read_to_string(root.join("crates/a/Cargo.toml")).unwrap();
+    assert!(member.contains("serde = { workspace = true"));
+    assert_eq!(
+        fs::read_to_string(root.join("crates/out/Cargo.toml")).unwrap(),
+        built
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("crates/.cache/Cargo.toml")).unwrap(),
+        cached
+    );
+    fs::remove_dir_all(root).unwrap();
+
+    // Cargo itself lists the generated crate as a member, which fails alone
+    let root = fixture("generated");
+    let built = generated_manifest(&root.join("crates/out"));
+    let output = easy_dep(&root, &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to update 1 member") && stderr.contains("target directory"),
+        "unexpected error:\n{stderr}"
+    );
+    let member = fs::read_to_string(root.join("crates/a/Cargo.toml")).unwrap();
+    assert!(member.contains("serde = { workspace = true"));
+    assert_eq!(
+        fs::read_to_string(root.join("crates/out/Cargo.toml")).unwrap(),
+        built
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn config_schema_covers_every_config_field() {
+    // Options the `Config` fields are set from, when not named after the field
+    let options = |field: &str| -> Option<Vec<&str>> {
+        Some(match field {
+            "workspace_roots" => vec!["workspace-root"],
+            "only_kinds" => vec!["only-normal", "only-dev", "only-build"],
+            "only_deps" => vec!["dep"],
+            "exclude" => vec!["exclude-normal", "exclude-dev", "exclude-build"],
+            "exclude_registries" => vec!["exclude-registry"],
+            "report_unused" => vec!["report-unused-workspace-deps"],
+            "update_root" => vec!["no-root-update"],
+            "update_members" => vec!["no-member-update"],
+            "root_diff" => vec!["output-root-only-diff"],
+            "confirm" => vec!["yes"],
+            "quiet" => vec!["quiet", "silent"],
+            "features_strategy" => vec!["features-strategy", "hoist-features", "dedupe-features"],
+            "table_style" => vec!["inline-tables", "section-tables"],
+            // Set by the `list` subcommand
+            "list" => vec![],
+            _ => return None,
+        })
+    };
+    // Options only shaping what the binary prints, not part of `Config`
+    let presentation = ["color", "no-banner", "no-hints", "stats", "summary-line"];
+
+    let config = serde_json::to_value(Config::default()).unwrap();
+    let mut expected: Vec<String> = presentation.iter().map(|key| key.to_string()).collect();
+    for field in config.as_object().unwrap().keys() {
+        match options(field) {
+            Some(keys) => expected.extend(keys.iter().map(|key| key.to_string())),
+            None => expected.push(field.replace('_', "-")),
+        }
+    }
+    expected.sort();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_cargo-easy-dep"))
+        .args(["easy-dep", "--print-config-schema"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let properties = schema["properties"].as_object().unwrap();
+    let mut keys: Vec<String> = properties.keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, expected);
+
+    // The defaults of the enum fields are among the values the schema accepts
+    for field in [
+        "on_conflict",
+        "features_strategy",
+        "version_source",
+        "format",
+    ] {
+        let values = &properties[&field.replace('_', "-")]["enum"];
+        assert!(
+            values.as_array().unwrap().contains(&config[field]),
+            "{field}: {} not in {values}",
+            config[field]
+        );
+    }
+}